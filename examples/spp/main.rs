@@ -57,10 +57,14 @@ impl MyDataSource {
                         // Note that if you apply a min_snr preset,
                         // we might drop candidates that do not have this info
                         snr: None, // unknown
+                        // RINEX observable (like "C1C"), used by signal priority policies
+                        code: None,
                     }],
                     // List of Phase Range observations: not needed in this scenario
                     vec![],
                 ),
+                // Doppler observations may be attached with .with_doppler(),
+                // they are needed to resolve instantaneous velocities
                 // Create all as many candidates as possible.
                 // It's better to have more than needed, it leaves us more possibility in the election process.
             ],
@@ -1,12 +1,28 @@
-use crate::prelude::{Candidate, Carrier, Duration, Epoch, SV}; // Error
-use log::{debug, error, warn};
+use crate::{
+    cfg::AmbiguityOpts,
+    cycle_slip::CycleSlip,
+    lambda::{self, IntegerSolution},
+    navigation::{FilterState, StateKind},
+    prelude::{Candidate, Carrier, SV}, // Error
+};
+use log::{debug, error};
+use nalgebra::{DMatrix, DVector};
 use nyx::cosmic::SPEED_OF_LIGHT;
-use polyfit_rs::polyfit_rs::polyfit;
 use std::collections::HashMap;
 
 /// Ambiguity, per SV and reference signal
 pub type Ambiguities = HashMap<(SV, Carrier), Ambiguity>;
 
+/// Continuous phase tracking of one SV (without cycle slip nor loss of sight)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PhaseArc {
+    /// Arc number, incremented on each cycle slip or loss of sight
+    pub id: u16,
+    /// A priori ionosphere free ambiguity [m]:
+    /// phase minus code combination, on first lock
+    pub apriori: f64,
+}
+
 #[derive(Clone, Debug)]
 pub struct Ambiguity {
     /// Reference signal ambiguity
@@ -15,12 +31,16 @@ pub struct Ambiguity {
     pub n_2: f64,
     /// MW ambiguity
     pub n_w: f64,
+    /// True when [Self::n_1] was fixed by integer least-squares
+    /// resolution and passed validation, false when it is a
+    /// rounded float estimate.
+    pub fixed: bool,
 }
 
-/// Data averager
-struct Averager {
-    y: f64,
-    sigma: f64,
+/// Data averager, with running standard deviation (Welford's algorithm)
+pub(crate) struct Averager {
+    mean: f64,
+    m2: f64,
     pub n: u64,
 }
 
@@ -28,258 +48,350 @@ impl Averager {
     /// Builds new Averager
     pub fn new() -> Self {
         Self {
-            y: 0.0,
-            sigma: 0.0,
+            mean: 0.0,
+            m2: 0.0,
             n: 0,
         }
     }
-    /// Updates average value, taking new `value` into account
-    pub fn average(&mut self, x: f64, sigma: f64) -> (f64, f64) {
-        self.y = (x + (self.n as f64) * self.y) / (self.n + 1) as f64;
-        self.sigma = (sigma + (self.n as f64) * self.sigma) / (self.n + 1) as f64;
+    /// Updates average value, taking new `value` into account.
+    /// Returns the average value and the standard deviation of the samples.
+    pub fn average(&mut self, x: f64) -> (f64, f64) {
         self.n += 1;
-        (self.y, self.sigma)
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+        (self.mean, self.std_dev())
     }
-    /// Hard reset
-    pub fn reset(&mut self) {
-        self.n = 0;
-        self.y = 0.0;
-        self.sigma = 0.0;
+    /// Current average value
+    pub fn mean(&self) -> f64 {
+        self.mean
     }
-}
-
-struct Buffer {
-    window: Duration,
-    gap_tolerance: Duration,
-    pub inner: Vec<(Epoch, f64)>,
-}
-
-impl Buffer {
-    /// Allocates a new Buffer
-    pub fn malloc(capacity: usize, window: Duration, gap_tolerance: Duration) -> Self {
-        Self {
-            window,
-            gap_tolerance,
-            inner: Vec::with_capacity(capacity),
+    /// Standard deviation of the samples
+    pub fn std_dev(&self) -> f64 {
+        if self.n > 1 {
+            (self.m2 / (self.n - 1) as f64).sqrt()
+        } else {
+            0.0
         }
     }
-    /// Push data into self
-    pub fn push(&mut self, t: Epoch, y: f64) {
-        if let Some((t_last, _)) = self.inner.last() {
-            if (t - *t_last) > self.gap_tolerance {
-                error!("{}: buffer reset on data gap", t);
-                self.reset();
-            }
-        }
-        self.inner.push((t, y));
-
-        let t0 = self.inner[0].0;
-        if (t - t0) > self.window {
-            self.inner.remove(0);
+    /// Standard deviation of the average value
+    pub fn std_mean(&self) -> f64 {
+        if self.n > 0 {
+            self.std_dev() / (self.n as f64).sqrt()
+        } else {
+            0.0
         }
     }
-    /// Resets self
+    /// Hard reset
     pub fn reset(&mut self) {
-        self.inner.clear();
-    }
-    /// Returns current number of symbols
-    pub fn len(&self) -> usize {
-        self.inner.len()
-    }
-    // /// Returns std_mean over self
-    // pub fn std_mean(&self) -> f64 {
-    //     let mut v = 0.0_f64;
-    //     for i in 0..self.inner.len() {
-    //         v += self.inner[i].1;
-    //     }
-    //     v / self.inner.len() as f64
-    // }
-    // /// Returns std_dev over self
-    // pub fn std_dev(&self) -> f64 {
-    //     let mean = self.std_mean();
-    //     let mut v = 0.0_f64;
-    //     for i in 0..self.inner.len() {
-    //         v += (self.inner[i].1 - mean).powi(2);
-    //     }
-    //     v / self.inner.len() as f64
-    // }
-    /// Performs polyfit (nth order) over self
-    pub fn polyfit(&self, order: usize) -> Option<Vec<f64>> {
-        if self.inner.len() > order {
-            let x_s = self
-                .inner
-                .iter()
-                .map(|(k, _)| k.duration.to_seconds())
-                .collect::<Vec<f64>>();
-            let y_s = self.inner.iter().map(|(_, v)| *v).collect::<Vec<f64>>();
-            match polyfit(&x_s, &y_s, order) {
-                Ok(fit) => Some(fit),
-                Err(e) => {
-                    warn!("polyfit error: {}", e);
-                    None
-                },
-            }
-        } else {
-            None
-        }
+        self.n = 0;
+        self.mean = 0.0;
+        self.m2 = 0.0;
     }
 }
 
 struct SVTracker {
-    /// last seen [Epoch]
-    pub last_seen: Option<Epoch>,
     /// MW tracker per [SV]
     pub mw_tracker: Averager,
     /// N_1 tracker per [SV]
     pub n1_tracker: Averager,
-    /// GF moving window
-    pub gf_buffer: Buffer,
+    /// Fixed wide lane ambiguity of current phase arc
+    pub n_w: Option<f64>,
+    /// Current phase arc number
+    pub arc: u16,
+    /// A priori ambiguity of current phase arc
+    pub arc_apriori: Option<f64>,
 }
 
 impl SVTracker {
-    pub fn new(last_seen: Epoch, gap_tolerance: Duration) -> Self {
+    pub fn new() -> Self {
         Self {
-            last_seen: Some(last_seen),
             n1_tracker: Averager::new(),
             mw_tracker: Averager::new(),
-            gf_buffer: Buffer::malloc(
-                128,
-                //TODO: programmble
-                Duration::from_seconds(1000.0),
-                gap_tolerance,
-            ),
+            n_w: None,
+            arc: 0,
+            arc_apriori: None,
         }
     }
-    pub fn reset(&mut self) {
-        self.n1_tracker.reset();
+    /// Declares a new phase arc (on cycle slip)
+    pub fn new_arc(&mut self) {
+        self.arc = self.arc.wrapping_add(1);
+        self.arc_apriori = None;
         self.mw_tracker.reset();
-        self.gf_buffer.reset();
+        self.n1_tracker.reset();
+        self.n_w = None;
     }
 }
 
 /// [AmbiguitySolver] resolves phase range ambiguities in real time.
 pub struct AmbiguitySolver {
-    /// Sampling interval [Duration]
-    gap_tolerance: Duration,
+    /// Tracking and resolution options
+    opts: AmbiguityOpts,
     /// [SV] tracker
     sv_trackers: HashMap<SV, SVTracker>,
-    /// [SV] out of sight
-    untracked: Vec<SV>,
 }
 
 impl AmbiguitySolver {
-    /// Builds new [AmbiguitySolver] to work with given sample interval [Duration].
-    pub fn new(gap_tolerance: Duration) -> Self {
+    /// Builds new [AmbiguitySolver] with given [AmbiguityOpts].
+    pub fn new(opts: &AmbiguityOpts) -> Self {
         Self {
-            gap_tolerance,
-            untracked: Vec::with_capacity(8),
+            opts: opts.clone(),
             sv_trackers: HashMap::with_capacity(8),
         }
     }
-    /// Resolve [Ambiguities]
-    pub fn resolve(&mut self, pool: &[Candidate]) -> Ambiguities {
+    /// Resolve [Ambiguities]. Phase tracking arcs interrupted
+    /// by a [CycleSlip] are restarted.
+    pub fn resolve(&mut self, pool: &[Candidate], slips: &[CycleSlip]) -> Ambiguities {
         let mut ambiguities = Ambiguities::with_capacity(pool.len());
 
         for cd in pool {
-            if self.sv_trackers.get(&cd.sv).is_none() {
-                self.sv_trackers
-                    .insert(cd.sv, SVTracker::new(cd.t, self.gap_tolerance));
-                self.untracked.retain(|sv| *sv != cd.sv);
+            let sv_tracker = self.sv_trackers.entry(cd.sv).or_insert_with(SVTracker::new);
+            if slips.iter().any(|slip| slip.sv == cd.sv) {
+                sv_tracker.new_arc();
             }
 
-            let sv_tracker = self.sv_trackers.get_mut(&cd.sv).unwrap();
-
-            // manage loss of sight
-            if let Some(last_seen) = sv_tracker.last_seen {
-                let dt = cd.t - last_seen;
-                if dt > self.gap_tolerance && !self.untracked.contains(&cd.sv) {
-                    warn!("{}({}): tracker reset - {} loss of sight", cd.t, cd.sv, dt);
-                    sv_tracker.reset();
-                    self.untracked.push(cd.sv);
-                } else {
-                    sv_tracker.last_seen = Some(cd.t);
-                    self.untracked.retain(|sv| *sv != cd.sv);
-                }
-            }
+            if let Some(cmb) = cd.mw_combination() {
+                let (f_1, f_j) = (cmb.reference.frequency(), cmb.lhs.frequency());
+                let lambda_w = SPEED_OF_LIGHT / (f_1 - f_j);
+                let min_samples = self.opts.min_samples as u64;
 
-            // proceed
-            if !self.untracked.contains(&cd.sv) {
-                if let Some(cmb) = cd.phase_gf_combination() {
-                    if let Some(fit) = sv_tracker.gf_buffer.polyfit(2) {
-                        let t = cd.t.duration.to_seconds();
-                        // let n = sv_tracker.gf_buffer.inner.len();
-                        // let dt = t - sv_tracker.gf_buffer.inner[n - 2].0.duration.to_seconds();
-                        let (a2, a1, a0) = (fit[2], fit[1], fit[0]);
-                        let predicted = a2 * t.powi(2) + a1 * t + a0;
-                        let err = (cmb.value - predicted).abs();
-                        // let t0 = 60.0;
-                        // let a0 = (cmb.lhs.wavelength() - cmb.reference.wavelength()) * 3.0 / 2.0;
-                        // let threshold = a0 - a0 / 2.0 * (-dt / t0).exp();
-                        let threshold = 5.0;
-                        if err > threshold {
-                            debug!(
-                                "{}({}) gf cycle slip declared: {}/{}",
-                                cd.t, cd.sv, err, threshold
-                            );
-                            sv_tracker.mw_tracker.reset();
-                        }
+                // wide lane: averaged MW combination
+                let (n_w, sigma_n_w) = sv_tracker.mw_tracker.average(cmb.value / lambda_w);
+                let sigma_mean = sv_tracker.mw_tracker.std_mean();
+                if sv_tracker.mw_tracker.n >= min_samples
+                    && (n_w - n_w.round()).abs() <= self.opts.wl_max_fraction
+                    && sigma_mean <= self.opts.wl_max_sigma
+                {
+                    let n_w = n_w.round();
+                    if sv_tracker.n_w != Some(n_w) {
+                        debug!(
+                            "{}({}): n_w: {} ({:.3}/{:.3})",
+                            cd.t, cd.sv, n_w, sigma_n_w, sigma_mean
+                        );
+                        // narrow lane depends on wide lane
+                        sv_tracker.n1_tracker.reset();
+                        sv_tracker.n_w = Some(n_w);
                     }
-                    sv_tracker.gf_buffer.push(cd.t, cmb.value);
-                } else {
-                    error!(
-                        "{}({}): failed to form gf comb (missing signal)",
-                        cd.t, cd.sv
-                    );
                 }
-                if let Some(cmb) = cd.mw_combination() {
-                    let (f_1, f_j) = (cmb.reference.frequency(), cmb.lhs.frequency());
-                    let lambda_w = SPEED_OF_LIGHT / (f_1 + f_j);
-                    let lamba_w = SPEED_OF_LIGHT / (f_1 - f_j);
-                    let (n_w, sigma_n_w) = sv_tracker.mw_tracker.average(cmb.value / lambda_w, 0.0);
-                    let n_w = n_w.round();
 
+                // narrow lane, once wide lane is fixed
+                if let Some(n_w) = sv_tracker.n_w {
                     let (l_1, l_j) = (cd.l1_phaserange().unwrap(), cd.lj_phaserange().unwrap());
                     let (lambda_1, lambda_2) = (l_1.carrier.wavelength(), l_j.carrier.wavelength());
-                    let (n_1, sigma_n_1) = sv_tracker.n1_tracker.average(
-                        (l_1.value - l_j.value - lambda_2 * n_w) / (lambda_1 - lambda_2),
-                        0.0,
-                    );
-
-                    let n_1 = n_1.round();
-                    let n_2 = (n_1 - n_w).round();
+                    let (n_1, sigma_n_1) = sv_tracker
+                        .n1_tracker
+                        .average((l_1.value - l_j.value - lambda_2 * n_w) / (lambda_1 - lambda_2));
 
-                    if sv_tracker.mw_tracker.n > 10 {
+                    if sv_tracker.n1_tracker.n >= min_samples {
+                        let n_1 = n_1.round();
+                        let n_2 = n_1 - n_w;
                         debug!(
-                            "{}({}): n_w: {}({}), n_1: {}({}) n_2: {}",
-                            cd.t, cd.sv, n_w, sigma_n_w, n_1, sigma_n_1, n_2
+                            "{}({}): n_w: {}, n_1: {}({:.3}) n_2: {}",
+                            cd.t, cd.sv, n_w, n_1, sigma_n_1, n_2
                         );
-
-                        let ambiguity = Ambiguity { n_1, n_2, n_w };
+                        let ambiguity = Ambiguity {
+                            n_1,
+                            n_2,
+                            n_w,
+                            fixed: false,
+                        };
                         ambiguities.insert((cd.sv, l_1.carrier), ambiguity);
                     }
-                } else {
-                    error!("{}({}): fail to form mw comb (missing signal)", cd.t, cd.sv);
+                }
+            } else {
+                error!("{}({}): fail to form mw comb (missing signal)", cd.t, cd.sv);
+            }
+
+            // first lock on this arc
+            if sv_tracker.arc_apriori.is_none() {
+                if let (Some(phase), Some(code)) =
+                    (cd.phase_if_combination(), cd.code_if_combination())
+                {
+                    sv_tracker.arc_apriori = Some(phase.value - code.value);
                 }
             }
         }
         ambiguities
     }
+    /// Returns current [PhaseArc] of this [SV], if it is being tracked
+    pub(crate) fn phase_arc(&self, sv: SV) -> Option<PhaseArc> {
+        let tracker = self.sv_trackers.get(&sv)?;
+        Some(PhaseArc {
+            id: tracker.arc,
+            apriori: tracker.arc_apriori?,
+        })
+    }
+}
+
+/// Integer ambiguity resolution outcome
+#[derive(Clone, Debug)]
+pub(crate) struct AmbiguityFix {
+    /// [SV] whose ambiguities were fixed, possibly a subset of the float ones
+    pub sv: Vec<SV>,
+    /// Ambiguity states that were fixed
+    pub kinds: Vec<StateKind>,
+    /// Float minus fixed ionosphere free ambiguities [m]
+    pub residuals: DVector<f64>,
+    /// Ratio test statistic of the fixed set
+    pub ratio: f64,
+    /// Bootstrapped success rate of the fixed set
+    pub success_rate: f64,
+}
+
+/*
+ * Integer least-squares resolution of float ambiguities `a` (covariance `q`),
+ * sorted by decreasing priority. When the complete set fails validation and
+ * partial fixing is allowed, lowest priority ambiguities are discarded one at a time.
+ * Returns the size of the validated subset and its integer solution.
+ */
+fn validated_subset(
+    a: &DVector<f64>,
+    q: &DMatrix<f64>,
+    opts: &AmbiguityOpts,
+) -> Option<(usize, IntegerSolution)> {
+    let mut n = a.len();
+    while n > 0 {
+        let a_n = a.rows(0, n).into_owned();
+        let q_n = q.view((0, 0), (n, n)).into_owned();
+        if let Some(solution) = lambda::resolve(&a_n, &q_n) {
+            let (ratio, success_rate) = (solution.ratio(), solution.success_rate);
+            if ratio >= opts.ratio_threshold && success_rate >= opts.min_success_rate {
+                return Some((n, solution));
+            }
+            debug!(
+                "{} ambiguities validation failed: ratio={:.3} success rate={:.5}",
+                n, ratio, success_rate
+            );
+        } else {
+            error!("integer ambiguity search failed");
+        }
+        if !opts.partial_fixing || n <= opts.min_partial_subset {
+            break;
+        }
+        n -= 1;
+    }
+    None
+}
+
+/*
+ * Integer narrow lane ambiguity resolution. Float narrow lane ambiguities
+ * are deduced from the float ionosphere free ambiguities estimated by the
+ * navigation filter and the wide lane ambiguities, then resolved with the
+ * LAMBDA method, possibly partially (highest elevations first).
+ * Validated integers are reported into `ambiguities`.
+ */
+pub(crate) fn fix_ambiguities(
+    pool: &[Candidate],
+    state: &FilterState,
+    opts: &AmbiguityOpts,
+    ambiguities: &mut Ambiguities,
+) -> Option<AmbiguityFix> {
+    // float narrow lane ambiguities [cycles]
+    let mut floats = pool
+        .iter()
+        .filter_map(|cd| {
+            let arc = cd.phase_arc?;
+            let cmb = cd.phase_if_combination()?;
+            let ambiguity = ambiguities.get(&(cd.sv, cmb.reference))?;
+            let kind = StateKind::Ambiguity(cd.sv, arc.id);
+            let correction = state.value(kind)?;
+            let (f_1, f_j) = (cmb.reference.frequency(), cmb.lhs.frequency());
+            let lambda_j = cmb.lhs.wavelength();
+            let (lambda_n, lambda_w) = (SPEED_OF_LIGHT / (f_1 + f_j), SPEED_OF_LIGHT / (f_1 - f_j));
+            // b_c = lambda_n * (n_1 + lambda_w / lambda_j * n_w)
+            let n_1 = (arc.apriori + correction) / lambda_n - lambda_w / lambda_j * ambiguity.n_w;
+            let elevation = cd.state.map(|state| state.elevation).unwrap_or(0.0);
+            Some(((cd.sv, cmb.reference), kind, n_1, lambda_n, elevation))
+        })
+        .collect::<Vec<_>>();
+    if floats.is_empty() {
+        return None;
+    }
+    // highest elevations first: discarded last on partial fixing
+    floats.sort_by(|a, b| b.4.total_cmp(&a.4));
+
+    let kinds = floats.iter().map(|f| f.1).collect::<Vec<_>>();
+    let lambdas = floats.iter().map(|f| f.3).collect::<Vec<_>>();
+    let a = DVector::<f64>::from_iterator(floats.len(), floats.iter().map(|f| f.2));
+    let p = state.covariance(&kinds)?;
+    let q = DMatrix::<f64>::from_fn(p.nrows(), p.ncols(), |i, j| {
+        p[(i, j)] / lambdas[i] / lambdas[j]
+    });
+
+    let (n, solution) = validated_subset(&a, &q, opts)?;
+    for (i, (key, ..)) in floats.iter().take(n).enumerate() {
+        if let Some(ambiguity) = ambiguities.get_mut(key) {
+            ambiguity.n_1 = solution.fixed[i];
+            ambiguity.n_2 = solution.fixed[i] - ambiguity.n_w;
+            ambiguity.fixed = true;
+        }
+    }
+    debug!(
+        "{}/{} ambiguities fixed: ratio={:.3} success rate={:.5}",
+        n,
+        floats.len(),
+        solution.ratio(),
+        solution.success_rate
+    );
+    Some(AmbiguityFix {
+        sv: floats.iter().take(n).map(|f| f.0 .0).collect(),
+        kinds: kinds[..n].to_vec(),
+        residuals: DVector::<f64>::from_fn(n, |i, _| lambdas[i] * (a[i] - solution.fixed[i])),
+        ratio: solution.ratio(),
+        success_rate: solution.success_rate,
+    })
 }
 
 #[cfg(test)]
 mod test {
-    use super::Averager;
+    use super::{validated_subset, Averager};
+    use crate::cfg::AmbiguityOpts;
+    use nalgebra::{DMatrix, DVector};
     #[test]
     fn test_averager() {
         let mut avg = Averager::new();
         for (value, expect) in [(1.0, 1.0)] {
             assert_eq!(
-                avg.average(value, 0.0).0,
+                avg.average(value).0,
                 expect,
                 "failed for +={}={}",
                 value,
                 expect
             );
         }
+        for value in [2.0, 3.0, 4.0] {
+            avg.average(value);
+        }
+        let (mean, sigma) = (avg.average(5.0).0, avg.std_dev());
+        assert_eq!(mean, 3.0);
+        assert!((sigma - 2.5_f64.sqrt()).abs() < 1.0E-12);
+        assert!((avg.std_mean() - 0.5_f64.sqrt()).abs() < 1.0E-12);
+        avg.reset();
+        assert_eq!(avg.average(2.0), (2.0, 0.0));
+    }
+    #[test]
+    fn partial_fixing() {
+        // last (lowest elevation) ambiguity is poorly determined
+        let a = DVector::<f64>::from_column_slice(&[3.01, -7.02, 12.0, 1.98, 4.5]);
+        let mut q = DMatrix::<f64>::identity(5, 5) * 1.0E-3;
+        q[(4, 4)] = 1.0;
+
+        let opts = AmbiguityOpts::default();
+        let (n, solution) = validated_subset(&a, &q, &opts).unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(solution.fixed.as_slice(), &[3.0, -7.0, 12.0, 2.0]);
+        assert!(solution.ratio() >= opts.ratio_threshold);
+
+        let opts = AmbiguityOpts {
+            partial_fixing: false,
+            ..Default::default()
+        };
+        assert!(validated_subset(&a, &q, &opts).is_none());
+
+        let opts = AmbiguityOpts {
+            min_partial_subset: 5,
+            ..Default::default()
+        };
+        assert!(validated_subset(&a, &q, &opts).is_none());
     }
 }
@@ -0,0 +1,476 @@
+//! Antenna phase center offsets and variations (ANTEX)
+use crate::{
+    carrier::rinex_constellation,
+    prelude::{Carrier, Constellation, Epoch, TimeScale, SV},
+};
+use nalgebra::Vector3;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// ANTEX parsing error
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum AntexError {
+    #[error("failed to read ANTEX file: {0}")]
+    Io(String),
+    #[error("line {0}: invalid \"{1}\" record")]
+    InvalidRecord(usize, String),
+    #[error("line {0}: unexpected \"{1}\" record")]
+    UnexpectedRecord(usize, String),
+}
+
+/// Phase center model of one antenna, on one frequency
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrequencyPattern {
+    /// Phase center offset [m]: North, East, Up for receiver antennas,
+    /// X, Y, Z (body frame) for satellite antennas
+    pco: Vector3<f64>,
+    /// First zenith (or nadir) angle of the grid [°]
+    zen1: f64,
+    /// Zenith (or nadir) angle increment [°]
+    dzen: f64,
+    /// Azimuth increment [°], null when variations do not depend on azimuth
+    dazi: f64,
+    /// Azimuth independent variations [m]
+    noazi: Vec<f64>,
+    /// Azimuth dependent variations [m], one row per azimuth increment
+    azi: Vec<Vec<f64>>,
+}
+
+/*
+ * Linear interpolation of gridded values, at given (fractional) index.
+ * Values are held constant beyond the grid.
+ */
+fn interpolate(values: &[f64], index: f64) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        1 => values[0],
+        len => {
+            let index = index.clamp(0.0, (len - 1) as f64);
+            let i = (index.floor() as usize).min(len - 2);
+            let frac = index - i as f64;
+            values[i] * (1.0 - frac) + values[i + 1] * frac
+        },
+    }
+}
+
+impl FrequencyPattern {
+    /// Phase center offset [m]: North, East, Up for receiver antennas,
+    /// X, Y, Z (body frame) for satellite antennas.
+    pub fn pco(&self) -> Vector3<f64> {
+        self.pco
+    }
+    /// Phase center variation [m], at given zenith angle (nadir angle for satellite
+    /// antennas) and azimuth [°]. Variations are interpolated over the ANTEX grid.
+    pub fn pcv(&self, zenith: f64, azimuth: f64) -> f64 {
+        let zen_index = if self.dzen > 0.0 {
+            (zenith - self.zen1) / self.dzen
+        } else {
+            0.0
+        };
+        if self.dazi > 0.0 && !self.azi.is_empty() {
+            let azi_index = azimuth.rem_euclid(360.0) / self.dazi;
+            let values = self
+                .azi
+                .iter()
+                .map(|row| interpolate(row, zen_index))
+                .collect::<Vec<_>>();
+            interpolate(&values, azi_index)
+        } else {
+            interpolate(&self.noazi, zen_index)
+        }
+    }
+}
+
+/// Antenna phase center correction, on one signal
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct PhaseCenter {
+    /// Offset from the reference point to the phase center, ECEF [m]
+    pub offset: Vector3<f64>,
+    /// Phase center variation, in the line of sight [m]
+    pub variation: f64,
+}
+
+impl PhaseCenter {
+    /*
+     * Ionosphere free combination of two phase center corrections
+     */
+    pub fn if_combination(&self, f_1: f64, rhs: &Self, f_j: f64) -> Self {
+        let (f1_2, fj_2) = (f_1.powi(2), f_j.powi(2));
+        let (a, b) = (f1_2 / (f1_2 - fj_2), fj_2 / (f1_2 - fj_2));
+        Self {
+            offset: self.offset * a - rhs.offset * b,
+            variation: self.variation * a - rhs.variation * b,
+        }
+    }
+}
+
+/// Antenna description and phase center model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Antenna {
+    /// Antenna type, including the radome code for receiver antennas
+    /// (like "TRM59800.00     NONE"), or satellite block
+    pub name: String,
+    /// Serial number, or [SV] identifier for satellite antennas
+    pub serial: String,
+    /// [SV] for satellite antennas
+    pub sv: Option<SV>,
+    /// Start of validity
+    pub valid_from: Option<Epoch>,
+    /// End of validity
+    pub valid_until: Option<Epoch>,
+    /// Phase center models, per frequency
+    patterns: Vec<(Constellation, Carrier, FrequencyPattern)>,
+}
+
+/*
+ * Frequencies are compared by band: Glonass FDMA channels share one model
+ */
+fn same_band(lhs: Carrier, rhs: Carrier) -> bool {
+    lhs.with_fdma_channel(0).frequency() == rhs.with_fdma_channel(0).frequency()
+}
+
+impl Antenna {
+    /// Returns the phase center model of this signal. GPS models are used
+    /// for other constellations transmitting on the same frequency, when
+    /// no dedicated model exists.
+    pub fn pattern(
+        &self,
+        constellation: Constellation,
+        carrier: Carrier,
+    ) -> Option<&FrequencyPattern> {
+        let find = |target: Constellation| {
+            self.patterns
+                .iter()
+                .find(|(c, freq, _)| *c == target && same_band(*freq, carrier))
+                .map(|(_, _, pattern)| pattern)
+        };
+        find(constellation).or_else(|| find(Constellation::GPS))
+    }
+    /*
+     * True if self is valid at this epoch
+     */
+    fn is_valid(&self, t: Epoch) -> bool {
+        let after = self.valid_from.map(|from| t >= from).unwrap_or(true);
+        let before = self.valid_until.map(|until| t < until).unwrap_or(true);
+        after && before
+    }
+}
+
+/// [Antex] holds the antenna models of an ANTEX file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Antex {
+    /// Antennas described in this file
+    pub antennas: Vec<Antenna>,
+}
+
+impl Antex {
+    /// Parses given ANTEX file.
+    pub fn from_file(path: &str) -> Result<Self, AntexError> {
+        let content = std::fs::read_to_string(path).map_err(|e| AntexError::Io(e.to_string()))?;
+        Self::from_str(&content)
+    }
+    /// Returns the antenna of this [SV], valid at this [Epoch].
+    pub fn sv_antenna(&self, sv: SV, t: Epoch) -> Option<&Antenna> {
+        self.antennas
+            .iter()
+            .find(|ant| ant.sv == Some(sv) && ant.is_valid(t))
+    }
+    /// Returns the receiver antenna of this type: antenna and radome codes
+    /// (like "TRM59800.00     NONE"). Blanks are not significant.
+    pub fn rx_antenna(&self, name: &str) -> Option<&Antenna> {
+        let name = name.split_whitespace().collect::<Vec<_>>();
+        self.antennas
+            .iter()
+            .find(|ant| ant.sv.is_none() && ant.name.split_whitespace().collect::<Vec<_>>() == name)
+    }
+}
+
+/*
+ * Carrier designated by an ANTEX frequency code (like "G01" or "E05")
+ */
+fn antex_frequency(code: &str) -> Option<(Constellation, Carrier)> {
+    let mut chars = code.trim().chars();
+    let constellation = rinex_constellation(chars.next()?).ok()?;
+    let band = chars.as_str().parse::<u8>().ok()?;
+    let carrier = match (constellation, band) {
+        (Constellation::GPS | Constellation::QZSS, 1) => Carrier::L1,
+        (Constellation::GPS | Constellation::QZSS, 2) => Carrier::L2,
+        (Constellation::GPS | Constellation::QZSS, 5) => Carrier::L5,
+        (Constellation::QZSS, 6) => Carrier::L6,
+        (Constellation::Glonass, 1) => Carrier::G1(0),
+        (Constellation::Glonass, 2) => Carrier::G2(0),
+        (Constellation::Glonass, 3) => Carrier::G3,
+        (Constellation::Glonass, 4) => Carrier::G1a,
+        (Constellation::Glonass, 6) => Carrier::G2a,
+        (Constellation::Galileo, 1) => Carrier::E1,
+        (Constellation::Galileo, 5) => Carrier::E5A,
+        (Constellation::Galileo, 6) => Carrier::E6,
+        (Constellation::Galileo, 7) => Carrier::E5B,
+        (Constellation::Galileo, 8) => Carrier::E5,
+        (Constellation::BeiDou, 1) => Carrier::B1aB1c,
+        (Constellation::BeiDou, 2) => Carrier::B1I,
+        (Constellation::BeiDou, 5) => Carrier::B2A,
+        (Constellation::BeiDou, 6) => Carrier::B3,
+        (Constellation::BeiDou, 7) => Carrier::B2iB2b,
+        (Constellation::BeiDou, 8) => Carrier::B2,
+        (Constellation::IRNSS, 5) => Carrier::NavicL5,
+        (Constellation::IRNSS, 9) => Carrier::NavicS,
+        (c, 1) if c.is_sbas() => Carrier::L1,
+        (c, 5) if c.is_sbas() => Carrier::L5,
+        _ => return None,
+    };
+    Some((constellation, carrier))
+}
+
+/*
+ * Parses whitespace separated numbers
+ */
+fn numbers(content: &str) -> Option<Vec<f64>> {
+    content
+        .split_whitespace()
+        .map(|item| item.parse::<f64>().ok())
+        .collect()
+}
+
+/*
+ * Parses a "VALID FROM" / "VALID UNTIL" record (GPS time)
+ */
+fn validity(content: &str) -> Option<Epoch> {
+    let items = numbers(content)?;
+    if items.len() < 6 {
+        return None;
+    }
+    let seconds = items[5];
+    Epoch::maybe_from_gregorian(
+        items[0] as i32,
+        items[1] as u8,
+        items[2] as u8,
+        items[3] as u8,
+        items[4] as u8,
+        seconds.trunc() as u8,
+        (seconds.fract() * 1.0E9).round() as u32,
+        TimeScale::GPST,
+    )
+    .ok()
+}
+
+/// ANTEX records we interpret
+const LABELS: [&str; 15] = [
+    "START OF ANTENNA",
+    "TYPE / SERIAL NO",
+    "DAZI",
+    "ZEN1 / ZEN2 / DZEN",
+    "VALID FROM",
+    "VALID UNTIL",
+    "START OF FREQUENCY",
+    "NORTH / EAST / UP",
+    "END OF FREQUENCY",
+    "START OF FREQ RMS",
+    "END OF FREQ RMS",
+    "END OF ANTENNA",
+    "END OF HEADER",
+    "COMMENT",
+    "METH / BY / # / DATE",
+];
+
+impl FromStr for Antex {
+    type Err = AntexError;
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let mut antennas = Vec::<Antenna>::new();
+        let mut antenna = None::<Antenna>;
+        let mut grid = (0.0_f64, 0.0_f64, 0.0_f64); // zen1, dzen, dazi
+        let mut frequency = None::<(Constellation, Carrier, FrequencyPattern)>;
+        let mut rms = false;
+
+        for (i, line) in content.lines().enumerate() {
+            let line_number = i + 1;
+            let (data, label) = match line.get(60..) {
+                Some(label) if LABELS.contains(&label.trim()) => (&line[..60], label.trim()),
+                _ => (line, ""),
+            };
+            let invalid = || AntexError::InvalidRecord(line_number, label.to_string());
+            let unexpected = || AntexError::UnexpectedRecord(line_number, label.to_string());
+
+            if rms {
+                rms = label != "END OF FREQ RMS";
+                continue;
+            }
+
+            match label {
+                "START OF ANTENNA" => {
+                    antenna = Some(Antenna::default());
+                    grid = (0.0, 0.0, 0.0);
+                },
+                "TYPE / SERIAL NO" => {
+                    let antenna = antenna.as_mut().ok_or_else(unexpected)?;
+                    antenna.name = data.get(..20).unwrap_or(data).trim().to_string();
+                    antenna.serial = data.get(20..40).unwrap_or_default().trim().to_string();
+                    let mut chars = antenna.serial.chars();
+                    antenna.sv = match (chars.next(), chars.as_str().parse::<u8>()) {
+                        (Some(id), Ok(prn)) if antenna.serial.len() == 3 => rinex_constellation(id)
+                            .ok()
+                            .map(|constellation| SV::new(constellation, prn)),
+                        _ => None,
+                    };
+                },
+                "DAZI" => {
+                    let items = numbers(data).ok_or_else(invalid)?;
+                    grid.2 = *items.first().ok_or_else(invalid)?;
+                },
+                "ZEN1 / ZEN2 / DZEN" => {
+                    let items = numbers(data).ok_or_else(invalid)?;
+                    if items.len() < 3 {
+                        return Err(invalid());
+                    }
+                    grid.0 = items[0];
+                    grid.1 = items[2];
+                },
+                "VALID FROM" => {
+                    let antenna = antenna.as_mut().ok_or_else(unexpected)?;
+                    antenna.valid_from = Some(validity(data).ok_or_else(invalid)?);
+                },
+                "VALID UNTIL" => {
+                    let antenna = antenna.as_mut().ok_or_else(unexpected)?;
+                    antenna.valid_until = Some(validity(data).ok_or_else(invalid)?);
+                },
+                "START OF FREQUENCY" => {
+                    if antenna.is_none() {
+                        return Err(unexpected());
+                    }
+                    // unknown frequencies are not retained
+                    frequency = antex_frequency(data).map(|(constellation, carrier)| {
+                        let pattern = FrequencyPattern {
+                            zen1: grid.0,
+                            dzen: grid.1,
+                            dazi: grid.2,
+                            ..Default::default()
+                        };
+                        (constellation, carrier, pattern)
+                    });
+                },
+                "NORTH / EAST / UP" => {
+                    if let Some((_, _, pattern)) = frequency.as_mut() {
+                        let items = numbers(data).ok_or_else(invalid)?;
+                        if items.len() < 3 {
+                            return Err(invalid());
+                        }
+                        pattern.pco = Vector3::new(items[0], items[1], items[2]) * 1.0E-3;
+                    }
+                },
+                "END OF FREQUENCY" => {
+                    let antenna = antenna.as_mut().ok_or_else(unexpected)?;
+                    if let Some(frequency) = frequency.take() {
+                        antenna.patterns.push(frequency);
+                    }
+                },
+                "START OF FREQ RMS" => rms = true,
+                "END OF ANTENNA" => {
+                    antennas.push(antenna.take().ok_or_else(unexpected)?);
+                },
+                "" => {
+                    // phase center variations
+                    if let Some((_, _, pattern)) = frequency.as_mut() {
+                        let trimmed = data.trim_start();
+                        if let Some(values) = trimmed.strip_prefix("NOAZI") {
+                            let values = numbers(values).ok_or(AntexError::InvalidRecord(
+                                line_number,
+                                "NOAZI".to_string(),
+                            ))?;
+                            pattern.noazi = values.iter().map(|v| v * 1.0E-3).collect();
+                        } else if !trimmed.is_empty() {
+                            let values = numbers(trimmed)
+                                .ok_or(AntexError::InvalidRecord(line_number, "AZI".to_string()))?;
+                            // first value is the azimuth
+                            pattern
+                                .azi
+                                .push(values.iter().skip(1).map(|v| v * 1.0E-3).collect());
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(Self { antennas })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Antex;
+    use crate::prelude::{Carrier, Constellation, Epoch, SV};
+    use std::str::FromStr;
+
+    const ANTEX: &str = "     1.4            M                                       ANTEX VERSION / SYST
+A                                                           PCV TYPE / REFANT
+                                                            END OF HEADER
+                                                            START OF ANTENNA
+BLOCK IIR-M         G05                 G050      2009-043A TYPE / SERIAL NO
+                                             0    29-JAN-17 METH / BY / # / DATE
+     0.0                                                    DAZI
+     0.0  17.0   1.0                                        ZEN1 / ZEN2 / DZEN
+     2                                                      # OF FREQUENCIES
+  2009     8    17     0     0    0.0000000                 VALID FROM
+   G01                                                      START OF FREQUENCY
+      0.00      0.00    800.00                              NORTH / EAST / UP
+   NOAZI    -0.80   -0.90   -0.90   -0.80   -0.40    0.20    0.80    1.30    1.40    1.20    0.70    0.00   -0.40   -0.70   -0.90   -0.90   -0.90   -0.90
+   G01                                                      END OF FREQUENCY
+   G02                                                      START OF FREQUENCY
+      0.00      0.00    800.00                              NORTH / EAST / UP
+   NOAZI    -0.80   -0.90   -0.90   -0.80   -0.40    0.20    0.80    1.30    1.40    1.20    0.70    0.00   -0.40   -0.70   -0.90   -0.90   -0.90   -0.90
+   G02                                                      END OF FREQUENCY
+                                                            END OF ANTENNA
+                                                            START OF ANTENNA
+TRM59800.00     NONE                                        TYPE / SERIAL NO
+   180.0                                                    DAZI
+     0.0  90.0  45.0                                        ZEN1 / ZEN2 / DZEN
+     1                                                      # OF FREQUENCIES
+   G01                                                      START OF FREQUENCY
+      1.50     -0.50     88.00                              NORTH / EAST / UP
+   NOAZI     0.00   -2.00    4.00
+     0.0     0.00   -1.00    2.00
+   180.0     0.00   -3.00    6.00
+   360.0     0.00   -1.00    2.00
+   G01                                                      END OF FREQUENCY
+   G01                                                      START OF FREQ RMS
+      0.10      0.10      0.10                              NORTH / EAST / UP
+   NOAZI     0.00    0.10    0.10
+   G01                                                      END OF FREQ RMS
+                                                            END OF ANTENNA
+";
+
+    #[test]
+    fn parsing() {
+        let antex = Antex::from_str(ANTEX).unwrap();
+        assert_eq!(antex.antennas.len(), 2);
+
+        let sv = SV::new(Constellation::GPS, 5);
+        let t = Epoch::from_str("2020-06-25T00:00:00 GPST").unwrap();
+        let antenna = antex.sv_antenna(sv, t).unwrap();
+        assert_eq!(antenna.name, "BLOCK IIR-M");
+        assert!(antex
+            .sv_antenna(sv, Epoch::from_str("2008-01-01T00:00:00 GPST").unwrap())
+            .is_none());
+
+        let pattern = antenna.pattern(Constellation::GPS, Carrier::L2P).unwrap();
+        assert_eq!(pattern.pco()[2], 0.8);
+        assert!((pattern.pcv(0.0, 0.0) + 0.8E-3).abs() < 1.0E-9);
+        assert!((pattern.pcv(7.5, 0.0) - 1.35E-3).abs() < 1.0E-9);
+        // beyond the grid
+        assert!((pattern.pcv(20.0, 0.0) + 0.9E-3).abs() < 1.0E-9);
+        assert!(antenna.pattern(Constellation::GPS, Carrier::L5).is_none());
+
+        let antenna = antex.rx_antenna("TRM59800.00 NONE").unwrap();
+        assert!(antenna.sv.is_none());
+        // GPS model used for Galileo E1
+        let pattern = antenna
+            .pattern(Constellation::Galileo, Carrier::E1)
+            .unwrap();
+        assert!((pattern.pco()[0] - 1.5E-3).abs() < 1.0E-9);
+        assert!((pattern.pco()[2] - 88.0E-3).abs() < 1.0E-9);
+        // azimuth dependent variations (RMS block is ignored)
+        assert!((pattern.pcv(45.0, 0.0) + 1.0E-3).abs() < 1.0E-9);
+        assert!((pattern.pcv(45.0, 90.0) + 2.0E-3).abs() < 1.0E-9);
+        assert!((pattern.pcv(67.5, 180.0) - 1.5E-3).abs() < 1.0E-9);
+        assert!((pattern.pcv(45.0, -180.0) + 3.0E-3).abs() < 1.0E-9);
+    }
+}
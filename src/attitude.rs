@@ -0,0 +1,332 @@
+//! Satellite attitude
+use crate::prelude::{Constellation, Epoch, SV};
+use log::debug;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Earth rotation rate [rad/s]
+const EARTH_ROTATION_RATE: f64 = 7.2921151467E-5;
+
+/// Maximal interruption [s] of the yaw tracking of one vehicle
+const MAX_GAP: f64 = 300.0;
+
+/// Satellite body frame unit vectors, expressed in ECEF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Attitude {
+    /// X axis: completes the right handed frame, towards the Sun side
+    pub x: Vector3<f64>,
+    /// Y axis: solar panels rotation axis
+    pub y: Vector3<f64>,
+    /// Z axis: antenna boresight, towards Earth center
+    pub z: Vector3<f64>,
+}
+
+/*
+ * Orbital frame: radial (Earth center to vehicle), along track
+ * and orbit normal unit vectors. `v_sv` is the ECEF velocity.
+ */
+fn orbital_frame(
+    r_sv: Vector3<f64>,
+    v_sv: Vector3<f64>,
+) -> Option<(Vector3<f64>, Vector3<f64>, Vector3<f64>)> {
+    // inertial velocity
+    let omega = Vector3::new(0.0, 0.0, EARTH_ROTATION_RATE);
+    let v_sv = v_sv + omega.cross(&r_sv);
+    let e_r = r_sv.try_normalize(0.0)?;
+    let n = r_sv.cross(&v_sv).try_normalize(0.0)?;
+    let e_t = n.cross(&e_r);
+    Some((e_r, e_t, n))
+}
+
+/*
+ * Wraps angle [rad] into ]-PI, PI]
+ */
+fn wrap(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(2.0 * PI);
+    if angle > PI {
+        angle - 2.0 * PI
+    } else {
+        angle
+    }
+}
+
+impl Attitude {
+    /*
+     * Nominal yaw steering attitude: Z axis towards Earth center, Y axis
+     * perpendicular to the Sun direction, so the solar panels face the Sun.
+     * `r_sv` and `r_sun` are ECEF positions, in the same unit.
+     * Returns None when the Sun, the vehicle and Earth center are aligned.
+     */
+    pub fn nominal(r_sv: Vector3<f64>, r_sun: Vector3<f64>) -> Option<Self> {
+        let z = -r_sv.try_normalize(0.0)?;
+        let e = (r_sun - r_sv).try_normalize(0.0)?;
+        let y = z.cross(&e).try_normalize(1.0E-12)?;
+        let x = y.cross(&z);
+        Some(Self { x, y, z })
+    }
+    /*
+     * Attitude with given yaw angle [rad]: angle between the X axis
+     * and the along track direction. Null yaw is the orbit normal mode.
+     */
+    pub fn from_yaw(r_sv: Vector3<f64>, v_sv: Vector3<f64>, yaw: f64) -> Option<Self> {
+        let (e_r, e_t, n) = orbital_frame(r_sv, v_sv)?;
+        let z = -e_r;
+        let x = e_t * yaw.cos() - n * yaw.sin();
+        let y = z.cross(&x);
+        Some(Self { x, y, z })
+    }
+    /*
+     * Yaw angle [rad] of self (see [Self::from_yaw])
+     */
+    pub fn yaw(&self, r_sv: Vector3<f64>, v_sv: Vector3<f64>) -> Option<f64> {
+        let (_, e_t, n) = orbital_frame(r_sv, v_sv)?;
+        Some((-self.x.dot(&n)).atan2(self.x.dot(&e_t)))
+    }
+}
+
+/// Yaw steering model of one vehicle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct YawModel {
+    /// Maximal yaw rate [rad/s], limiting noon and midnight turns
+    pub max_rate: f64,
+    /// Yaw rate [rad/s] while in Earth shadow: the vehicle keeps on
+    /// turning in the same direction, until nominal yaw is recovered.
+    /// None when the vehicle keeps on tracking the nominal yaw in shadow.
+    pub shadow_rate: Option<f64>,
+    /// Orbit normal mode is used when the Sun elevation above
+    /// the orbital plane is lower than this value [rad]
+    pub orbit_normal_beta: Option<f64>,
+    /// Orbit normal mode is always used
+    pub orbit_normal: bool,
+}
+
+impl YawModel {
+    /*
+     * Yaw steering model of this vehicle. Vehicles blocks are not known:
+     * GPS vehicles follow the block IIR model, BeiDou vehicles
+     * are identified by PRN (GEO, BDS-2 and BDS-3).
+     */
+    pub fn new(sv: SV) -> Self {
+        let deg = PI / 180.0;
+        let nominal = Self {
+            max_rate: 0.2 * deg,
+            shadow_rate: None,
+            orbit_normal_beta: None,
+            orbit_normal: false,
+        };
+        match sv.constellation {
+            Constellation::Glonass => Self {
+                max_rate: 0.25 * deg,
+                shadow_rate: Some(0.25 * deg),
+                ..nominal
+            },
+            Constellation::Galileo => Self {
+                max_rate: 0.203 * deg,
+                ..nominal
+            },
+            Constellation::BeiDou => match sv.prn {
+                1..=5 | 59..=63 => Self {
+                    orbit_normal: true,
+                    ..nominal
+                },
+                6..=18 => Self {
+                    orbit_normal_beta: Some(4.0 * deg),
+                    ..nominal
+                },
+                _ => Self {
+                    max_rate: 0.085 * deg,
+                    ..nominal
+                },
+            },
+            Constellation::QZSS => Self {
+                orbit_normal_beta: Some(20.0 * deg),
+                ..nominal
+            },
+            _ => nominal,
+        }
+    }
+}
+
+/// Yaw state of one vehicle
+#[derive(Debug, Clone, Copy)]
+struct YawState {
+    t: Epoch,
+    yaw: f64,
+    rate: f64,
+}
+
+/// [AttitudeTracker] models the yaw steering of each vehicle,
+/// including noon and midnight turns and eclipse seasons.
+#[derive(Default)]
+pub(crate) struct AttitudeTracker {
+    states: HashMap<SV, YawState>,
+}
+
+impl AttitudeTracker {
+    /*
+     * Returns attitude of this vehicle at `t`, given its ECEF position [m]
+     * and velocity [m/s], the Sun position (ECEF [m]) and whether the vehicle
+     * is in Earth shadow. Nominal attitude is returned when velocity is unknown.
+     */
+    pub fn attitude(
+        &mut self,
+        sv: SV,
+        t: Epoch,
+        r_sv: Vector3<f64>,
+        v_sv: Option<Vector3<f64>>,
+        r_sun: Vector3<f64>,
+        eclipsed: bool,
+    ) -> Option<Attitude> {
+        let nominal = Attitude::nominal(r_sv, r_sun);
+        let v_sv = match v_sv {
+            Some(v_sv) => v_sv,
+            None => return nominal,
+        };
+        let model = YawModel::new(sv);
+        let (_, _, n) = orbital_frame(r_sv, v_sv)?;
+        let beta = n.dot(&r_sun.try_normalize(0.0)?).asin();
+
+        let prev = self
+            .states
+            .get(&sv)
+            .filter(|prev| t > prev.t && (t - prev.t).to_seconds() < MAX_GAP);
+
+        let orbit_normal = model.orbit_normal
+            || model
+                .orbit_normal_beta
+                .map(|threshold| beta.abs() < threshold)
+                .unwrap_or(false);
+
+        let target = if orbit_normal {
+            Some(0.0)
+        } else {
+            nominal.and_then(|nominal| nominal.yaw(r_sv, v_sv))
+        };
+
+        let (yaw, rate) = match (prev, target) {
+            (Some(prev), target) => {
+                let dt = (t - prev.t).to_seconds();
+                let target = target.unwrap_or(prev.yaw);
+                let delta = wrap(target - prev.yaw);
+                let yaw = match model.shadow_rate {
+                    Some(shadow_rate) if eclipsed && !orbit_normal => {
+                        // keep on turning in the same direction
+                        let direction = if prev.rate != 0.0 {
+                            prev.rate.signum()
+                        } else {
+                            delta.signum()
+                        };
+                        if delta.signum() == direction && delta.abs() <= shadow_rate * dt {
+                            target
+                        } else {
+                            prev.yaw + direction * shadow_rate * dt
+                        }
+                    },
+                    _ if orbit_normal => target,
+                    _ => {
+                        let step = model.max_rate * dt;
+                        prev.yaw + delta.clamp(-step, step)
+                    },
+                };
+                let yaw = wrap(yaw);
+                (yaw, wrap(yaw - prev.yaw) / dt)
+            },
+            (None, Some(target)) => (target, 0.0),
+            (None, None) => return None,
+        };
+
+        if let Some(target) = target {
+            if wrap(target - yaw).abs() > 1.0E-6 {
+                debug!(
+                    "{} ({}): yaw maneuver {:.3}° (nominal {:.3}°)",
+                    t,
+                    sv,
+                    yaw.to_degrees(),
+                    target.to_degrees()
+                );
+            }
+        }
+
+        self.states.insert(sv, YawState { t, yaw, rate });
+        Attitude::from_yaw(r_sv, v_sv, yaw)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Attitude, AttitudeTracker};
+    use crate::prelude::{Constellation, Duration, Epoch, SV};
+    use nalgebra::Vector3;
+    #[test]
+    fn nominal_attitude() {
+        let r_sv = Vector3::new(2.6E7, 0.0, 0.0);
+        let r_sun = Vector3::new(0.0, 1.5E11, 0.0);
+        let attitude = Attitude::nominal(r_sv, r_sun).unwrap();
+        assert!((attitude.z - Vector3::new(-1.0, 0.0, 0.0)).norm() < 1.0E-9);
+        // X axis points towards the Sun side
+        assert!(attitude.x[1] > 0.99);
+        assert!(attitude.y.dot(&(r_sun - r_sv)).abs() < 1.0E-3);
+        // right handed
+        assert!((attitude.x.cross(&attitude.y) - attitude.z).norm() < 1.0E-9);
+
+        // eclipse noon / midnight singularity
+        let r_sun = Vector3::new(1.5E11, 0.0, 0.0);
+        assert!(Attitude::nominal(r_sv, r_sun).is_none());
+
+        // yaw representation
+        let v_sv = Vector3::new(0.0, 0.0, 3.9E3);
+        let r_sun = Vector3::new(0.3E11, 1.5E11, 0.2E11);
+        let attitude = Attitude::nominal(r_sv, r_sun).unwrap();
+        let yaw = attitude.yaw(r_sv, v_sv).unwrap();
+        let rebuilt = Attitude::from_yaw(r_sv, v_sv, yaw).unwrap();
+        assert!((rebuilt.x - attitude.x).norm() < 1.0E-9);
+        assert!((rebuilt.y - attitude.y).norm() < 1.0E-9);
+    }
+    #[test]
+    fn noon_turn() {
+        // circular orbit in the equatorial plane, Sun slightly above
+        // the orbital plane: nominal yaw flips by 180° at orbit noon
+        let radius = 2.656E7;
+        let omega = 1.458E-4;
+        let r_sun = Vector3::new(1.5E11, 0.0, 1.5E11 * 0.5_f64.to_radians().tan());
+        let t0 = Epoch::default();
+        for (sv, max_rate) in [
+            (SV::new(Constellation::GPS, 1), 0.2),
+            (SV::new(Constellation::Glonass, 1), 0.25),
+        ] {
+            let mut tracker = AttitudeTracker::default();
+            let mut prev_yaw = None::<f64>;
+            let mut max_step = 0.0_f64;
+            for i in -300..300 {
+                // inertial angle, relative to the Sun direction
+                let u = omega * 10.0 * i as f64;
+                let r_sv = Vector3::new(u.cos(), u.sin(), 0.0) * radius;
+                let v_sv = Vector3::new(-u.sin(), u.cos(), 0.0) * radius * omega;
+                let v_sv = v_sv - Vector3::new(0.0, 0.0, 7.2921151467E-5).cross(&r_sv);
+                let t = t0 + Duration::from_seconds(10.0 * i as f64);
+                let attitude = tracker
+                    .attitude(sv, t, r_sv, Some(v_sv), r_sun, false)
+                    .unwrap();
+                let yaw = attitude.yaw(r_sv, v_sv).unwrap();
+                if let Some(prev) = prev_yaw {
+                    let step = super::wrap(yaw - prev).abs().to_degrees() / 10.0;
+                    max_step = max_step.max(step);
+                }
+                prev_yaw = Some(yaw);
+            }
+            // nominal yaw rate at noon exceeds the maximal rate
+            assert!(max_step <= max_rate + 1.0E-9, "{}: {}°/s", sv, max_step);
+            assert!(max_step > max_rate * 0.99, "{}: noon turn not modeled", sv);
+        }
+        // orbit normal mode
+        let sv = SV::new(Constellation::BeiDou, 1);
+        let mut tracker = AttitudeTracker::default();
+        let r_sv = Vector3::new(0.0, 4.2E7, 0.0);
+        let v_sv = Vector3::new(-3.07E3, 0.0, 0.0);
+        let attitude = tracker
+            .attitude(sv, t0, r_sv, Some(v_sv), r_sun, false)
+            .unwrap();
+        assert!(attitude.yaw(r_sv, v_sv).unwrap().abs() < 1.0E-9);
+    }
+}
@@ -1,10 +1,18 @@
 //! Position solving candidate
-use crate::prelude::{Carrier, Config, Duration, Epoch, Error, InterpolationResult, Vector3, SV};
+use crate::{
+    ambiguity::PhaseArc,
+    antex::{Antenna, PhaseCenter},
+    attitude::Attitude,
+    navigation::solutions::enu_rotation,
+    prelude::{Carrier, Config, Duration, Epoch, Error, InterpolationResult, Vector3, SV},
+};
 use hifitime::Unit;
 use itertools::Itertools;
 use log::debug;
-use nyx::cosmic::SPEED_OF_LIGHT;
+use nyx::cosmic::{eclipse::EclipseState, SPEED_OF_LIGHT};
 use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::f64::consts::PI;
 
 /// Phase range observation to attach to each candidate
 #[derive(Debug, Default, PartialEq, Clone)]
@@ -20,6 +28,19 @@ pub struct PhaseRange {
     pub ambiguity: Option<f64>,
     /// Optional (but recommended) SNR in [dB]
     pub snr: Option<f64>,
+    /// Optional RINEX observable (like "L1C"), identifying the tracking mode.
+    /// Required by signal priority policies (see [crate::prelude::SignalPriority]).
+    /// Observations whose observable does not match the carrier are dropped.
+    /// Phase and code observations are combined on the same tracking mode.
+    pub code: Option<String>,
+    /// Optional Loss of Lock Indicator, as reported by the receiver
+    /// (like RINEX LLI flags). Bit 0 asserted means phase lock was lost
+    /// since previous observation: a cycle slip is then declared.
+    pub lli: Option<u8>,
+    /// Optional lock time, as reported by the receiver: continuous
+    /// phase tracking duration. A cycle slip is declared when it is shorter
+    /// than the time elapsed since previous observation.
+    pub lock_time: Option<Duration>,
 }
 
 /// Pseudo range observation to attach to each candidate
@@ -31,6 +52,60 @@ pub struct PseudoRange {
     pub value: f64,
     /// Optional (but recommended) SNR in [dB]
     pub snr: Option<f64>,
+    /// Optional RINEX observable (like "C1C"), identifying the tracking mode.
+    /// Required by signal priority policies (see [crate::prelude::SignalPriority]).
+    /// Observations whose observable does not match the carrier are dropped.
+    pub code: Option<String>,
+}
+
+/// Doppler observation to attach to each candidate
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Doppler {
+    /// Carrier signal
+    pub carrier: Carrier,
+    /// Measured Doppler shift [Hz], positive for approaching vehicles
+    pub value: f64,
+    /// Optional (but recommended) SNR in [dB]
+    pub snr: Option<f64>,
+}
+
+/*
+ * Tracking mode (like "1C") of a RINEX observable (like "C1C")
+ */
+fn tracking_mode(code: &str) -> &str {
+    let code = code.trim();
+    match code.char_indices().nth(1) {
+        Some((index, _)) if code.len() == 3 => &code[index..],
+        _ => code,
+    }
+}
+
+/*
+ * True if this signal is the primary signal of dual frequency combinations:
+ * signals of the L1 band, or NavIC L5 when no L1 band signal was observed
+ * (NavIC L5 + S pairs). Otherwise, NavIC L5 is a secondary signal.
+ * - l1_band: true when one signal of the L1 band was observed
+ */
+fn is_primary(carrier: Carrier, l1_band: bool) -> bool {
+    carrier.is_l1() || (carrier == Carrier::NavicL5 && !l1_band)
+}
+
+/*
+ * True if an observation of given tracking code (like "C1W") may be combined
+ * with one of the observations of the other kind (phase or code) on the same signal:
+ * tracking modes need to be identical, when both are known.
+ * Always true when the other kind was not observed.
+ */
+fn paired(code: &Option<String>, others: &[&Option<String>]) -> bool {
+    let code = match code {
+        Some(code) => code,
+        None => return true,
+    };
+    others.is_empty()
+        || others.iter().any(|other| match other {
+            Some(other) => tracking_mode(code) == tracking_mode(other),
+            None => true,
+        })
 }
 
 /// Combination of observations
@@ -76,6 +151,24 @@ pub struct Candidate {
     pub(crate) pseudo_range: Vec<PseudoRange>,
     // Phase range observations
     pub(crate) phase_range: Vec<PhaseRange>,
+    // Doppler observations
+    pub(crate) doppler: Vec<Doppler>,
+    // Pseudo Range observation variance [m²], provided by user
+    pub(crate) code_variance: Option<f64>,
+    // Phase Range observation variance [m²], provided by user
+    pub(crate) phase_variance: Option<f64>,
+    // Tracking modes (like "1C"), by decreasing priority
+    pub(crate) signal_priority: Vec<String>,
+    // Current phase tracking arc
+    pub(crate) phase_arc: Option<PhaseArc>,
+    // Eclipse state of the vehicle
+    pub(crate) eclipse: Option<EclipseState>,
+    // Modeled attitude of the vehicle
+    pub(crate) attitude: Option<Attitude>,
+    // Satellite antenna phase center corrections, per signal
+    pub(crate) sv_phase_center: HashMap<Carrier, PhaseCenter>,
+    // Receiver antenna phase center corrections, per signal
+    pub(crate) rx_phase_center: HashMap<Carrier, PhaseCenter>,
 }
 
 impl Candidate {
@@ -104,10 +197,40 @@ impl Candidate {
             tgd,
             pseudo_range,
             phase_range,
+            doppler: Vec::new(),
+            code_variance: None,
+            phase_variance: None,
+            signal_priority: Vec::new(),
+            phase_arc: None,
             state: None,
             wind_up: 0.0_f64,
+            eclipse: None,
+            attitude: None,
+            sv_phase_center: HashMap::new(),
+            rx_phase_center: HashMap::new(),
         }
     }
+    /// Attaches Doppler observations, used to resolve the instantaneous velocity.
+    /// SV velocities need to be provided as well (see [InterpolationResult]).
+    pub fn with_doppler(&self, doppler: Vec<Doppler>) -> Self {
+        let mut s = self.clone();
+        s.doppler = doppler;
+        s
+    }
+    /// Defines the variance [m²] of the Pseudo Range observation we will use,
+    /// when observations are weighted with [crate::prelude::WeightMatrix::Covar].
+    pub fn with_code_variance(&self, variance: f64) -> Self {
+        let mut s = self.clone();
+        s.code_variance = Some(variance);
+        s
+    }
+    /// Defines the variance [m²] of the Phase Range observation we will use,
+    /// when observations are weighted with [crate::prelude::WeightMatrix::Covar].
+    pub fn with_phase_variance(&self, variance: f64) -> Self {
+        let mut s = self.clone();
+        s.phase_variance = Some(variance);
+        s
+    }
     /*
      * Returns best observed SNR, whatever the signal
      */
@@ -127,14 +250,81 @@ impl Candidate {
             })
             .map(|c| c.snr)?
     }
+    /*
+     * Drops observations whose tracking code is not a valid RINEX observable
+     * of this constellation, or does not match the observation kind
+     * ("C" pseudo range, "L" phase range) or the declared carrier.
+     */
+    pub(crate) fn validate_codes(&mut self) {
+        let (t, sv) = (self.t, self.sv);
+        let valid = |code: &Option<String>, kind: char, carrier: Carrier| match code {
+            Some(code) => {
+                let matching = code.trim().starts_with(kind)
+                    && Carrier::from_observable(sv.constellation, code)
+                        .map(|parsed| parsed == carrier.with_fdma_channel(0))
+                        .unwrap_or(false);
+                if !matching {
+                    debug!(
+                        "{} ({}): invalid {} observable \"{}\"",
+                        t, sv, carrier, code
+                    );
+                }
+                matching
+            },
+            None => true,
+        };
+        self.pseudo_range
+            .retain(|pr| valid(&pr.code, 'C', pr.carrier));
+        self.phase_range
+            .retain(|ph| valid(&ph.code, 'L', ph.carrier));
+    }
+    /*
+     * Applies a signal priority policy: tracking modes (like "1C")
+     * by decreasing priority. Observations whose tracking mode
+     * is known but not listed are dropped.
+     */
+    pub(crate) fn apply_signal_priority(&mut self, codes: &[String]) {
+        let listed = |code: &Option<String>| match code {
+            Some(code) => codes
+                .iter()
+                .any(|c| tracking_mode(code) == tracking_mode(c)),
+            None => true,
+        };
+        self.pseudo_range.retain(|pr| listed(&pr.code));
+        self.phase_range.retain(|ph| listed(&ph.code));
+        self.signal_priority = codes.to_vec();
+    }
+    /*
+     * Rank of this observation in the signal priority policy (lowest is preferred).
+     * Observations of unknown tracking mode come last.
+     */
+    fn signal_rank(&self, code: &Option<String>) -> usize {
+        code.as_ref()
+            .and_then(|code| {
+                self.signal_priority
+                    .iter()
+                    .position(|c| tracking_mode(code) == tracking_mode(c))
+            })
+            .unwrap_or(self.signal_priority.len())
+    }
     /*
      * Returns one pseudo range observation [m], whatever the frequency.
-     * Best SNR is preferred though (if such information was provided).
+     * The signal priority policy applies first, then best SNR is preferred
+     * (if such information was provided).
      */
     pub fn prefered_pseudorange(&self) -> Option<PseudoRange> {
+        let rank = self
+            .pseudo_range
+            .iter()
+            .map(|pr| self.signal_rank(&pr.code))
+            .min()?;
         let mut snr = Option::<f64>::None;
         let mut pr = Option::<PseudoRange>::None;
-        for c in &self.pseudo_range {
+        for c in self
+            .pseudo_range
+            .iter()
+            .filter(|c| self.signal_rank(&c.code) == rank)
+        {
             if pr.is_none() {
                 pr = Some(c.clone());
                 snr = c.snr;
@@ -156,79 +346,114 @@ impl Candidate {
         }
         pr
     }
+    /*
+     * Returns range rate [m/s], from one Doppler observation, whatever the frequency.
+     * Best SNR is preferred though (if such information was provided).
+     */
+    pub(crate) fn range_rate(&self) -> Option<f64> {
+        let doppler = self.doppler.iter().max_by(|a, b| match (a.snr, b.snr) {
+            (Some(snr_a), Some(snr_b)) => snr_a.total_cmp(&snr_b),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        })?;
+        Some(-doppler.value * doppler.carrier.wavelength())
+    }
     // True if Self is Method::CPP compatible
     pub(crate) fn cpp_compatible(&self) -> bool {
         self.dual_pseudorange()
     }
+    /*
+     * Returns Glonass FDMA frequency channel number
+     */
+    pub(crate) fn fdma_channel(&self) -> Option<i8> {
+        self.pseudo_range
+            .iter()
+            .find_map(|pr| pr.carrier.fdma_channel())
+    }
     // True if Self is Method::PPP compatible
     pub(crate) fn ppp_compatible(&self) -> bool {
         self.dual_pseudorange() && self.dual_phase()
     }
-    // True if dual PR is present
+    // True if dual PR is present: one primary (L1) and one secondary (Lj) signal,
+    // as required by the dual frequency combinations
     pub(crate) fn dual_pseudorange(&self) -> bool {
-        self.pseudo_range
-            .iter()
-            .map(|c| (c.carrier.frequency() / 1.0E6) as u16)
-            .unique()
-            .count()
-            > 1
+        self.l1_pseudorange().is_some() && self.lj_pseudorange().is_some()
     }
-    // True if dual phase is present
+    // True if dual phase is present: one primary (L1) and one secondary (Lj) signal
     pub(crate) fn dual_phase(&self) -> bool {
-        self.phase_range
+        self.l1_phaserange().is_some() && self.lj_phaserange().is_some()
+    }
+    // True if one signal of the L1 band was observed
+    fn l1_band(&self) -> bool {
+        self.pseudo_range
             .iter()
-            .map(|c| (c.carrier.frequency() / 1.0E6) as u16)
-            .unique()
-            .count()
-            > 1
+            .map(|p| p.carrier)
+            .chain(self.phase_range.iter().map(|p| p.carrier))
+            .any(|carrier| carrier.is_l1())
     }
-    // Returns the L1 Pseudo Range observation [m] if it exists
-    pub(crate) fn l1_pseudorange(&self) -> Option<&PseudoRange> {
+    // Returns the preferred Pseudo Range observation on the primary
+    // (or secondary) signal, that pairs with one Phase Range observation.
+    fn pseudorange(&self, primary: bool) -> Option<&PseudoRange> {
+        let l1_band = self.l1_band();
+        let phases = self
+            .phase_range
+            .iter()
+            .filter(|p| is_primary(p.carrier, l1_band) == primary)
+            .map(|p| &p.code)
+            .collect::<Vec<_>>();
         self.pseudo_range
             .iter()
-            .filter(|p| {
-                matches!(
-                    p.carrier,
-                    Carrier::L1 | Carrier::E1 | Carrier::B1aB1c | Carrier::B1I
-                )
-            })
-            .reduce(|k, _| k)
+            .filter(|p| is_primary(p.carrier, l1_band) == primary && paired(&p.code, &phases))
+            .min_by_key(|p| self.selection_key(p.carrier, &p.code))
     }
-    // Returns the L1 Phase Range observation [m] if it exists
-    pub(crate) fn l1_phaserange(&self) -> Option<&PhaseRange> {
+    // Returns the preferred Phase Range observation on the primary
+    // (or secondary) signal, that pairs with one Pseudo Range observation.
+    fn phaserange(&self, primary: bool) -> Option<&PhaseRange> {
+        let l1_band = self.l1_band();
+        let codes = self
+            .pseudo_range
+            .iter()
+            .filter(|p| is_primary(p.carrier, l1_band) == primary)
+            .map(|p| &p.code)
+            .collect::<Vec<_>>();
         self.phase_range
             .iter()
-            .filter(|p| {
-                matches!(
-                    p.carrier,
-                    Carrier::L1 | Carrier::E1 | Carrier::B1aB1c | Carrier::B1I
-                )
-            })
-            .reduce(|k, _| k)
+            .filter(|p| is_primary(p.carrier, l1_band) == primary && paired(&p.code, &codes))
+            .min_by_key(|p| self.selection_key(p.carrier, &p.code))
     }
-    // Returns the Lj Pseudo Range observation [m] if it exists
+    // Selection key (lowest is preferred) of one observation: signal priority policy,
+    // then signal priority. Tracking mode breaks ties, so code and phase agree.
+    fn selection_key<'a>(
+        &self,
+        carrier: Carrier,
+        code: &'a Option<String>,
+    ) -> (usize, u8, Option<&'a str>) {
+        (
+            self.signal_rank(code),
+            carrier.priority(),
+            code.as_deref().map(tracking_mode),
+        )
+    }
+    // Returns the L1 Pseudo Range observation [m] if it exists.
+    // Preferred signal is selected when several are available.
+    pub(crate) fn l1_pseudorange(&self) -> Option<&PseudoRange> {
+        self.pseudorange(true)
+    }
+    // Returns the L1 Phase Range observation [m] if it exists.
+    // Preferred signal is selected when several are available.
+    pub(crate) fn l1_phaserange(&self) -> Option<&PhaseRange> {
+        self.phaserange(true)
+    }
+    // Returns the Lj Pseudo Range observation [m] if it exists:
+    // preferred signal on a secondary frequency.
     pub(crate) fn lj_pseudorange(&self) -> Option<&PseudoRange> {
-        self.pseudo_range
-            .iter()
-            .filter(|p| {
-                !matches!(
-                    p.carrier,
-                    Carrier::L1 | Carrier::E1 | Carrier::B1aB1c | Carrier::B1I
-                )
-            })
-            .reduce(|k, _| k)
+        self.pseudorange(false)
     }
-    // Returns the Lj Phase Range observation [m] if it exists
+    // Returns the Lj Phase Range observation [m] if it exists:
+    // preferred signal on a secondary frequency.
     pub(crate) fn lj_phaserange(&self) -> Option<&PhaseRange> {
-        self.phase_range
-            .iter()
-            .filter(|p| {
-                !matches!(
-                    p.carrier,
-                    Carrier::L1 | Carrier::E1 | Carrier::B1aB1c | Carrier::B1I
-                )
-            })
-            .reduce(|k, _| k)
+        self.phaserange(false)
     }
     /// Returns IF code range combination
     pub fn code_if_combination(&self) -> Option<PseudoRangeCombination> {
@@ -236,11 +461,7 @@ impl Candidate {
         let (c_l1, l1_signal) = (l1_pr.value, l1_pr.carrier);
         let freq_l1 = l1_signal.frequency();
 
-        let lx_pr = self
-            .pseudo_range
-            .iter()
-            .filter(|p| p.carrier != l1_pr.carrier)
-            .reduce(|k, _| k)?;
+        let lx_pr = self.lj_pseudorange()?;
 
         let (c_lx, lx_signal) = (lx_pr.value, lx_pr.carrier);
         let freq_lx = lx_signal.frequency();
@@ -262,11 +483,7 @@ impl Candidate {
         let (c_l1, l1_signal) = (l1_ph.value, l1_ph.carrier);
         let f_l1 = l1_signal.frequency();
 
-        let lx_ph = self
-            .phase_range
-            .iter()
-            .filter(|p| p.carrier != l1_ph.carrier)
-            .reduce(|k, _| k)?;
+        let lx_ph = self.lj_phaserange()?;
 
         let (c_lx, lx_signal) = (lx_ph.value, lx_ph.carrier);
         let f_lx = lx_signal.frequency();
@@ -284,11 +501,7 @@ impl Candidate {
     /// Returns phase wide lane combination
     pub(crate) fn phase_wl_combination(&self) -> Option<PhaseCombination> {
         let l_1 = self.l1_phaserange()?;
-        let l_j = self
-            .phase_range
-            .iter()
-            .filter(|p| p.carrier != l_1.carrier)
-            .reduce(|k, _| k)?;
+        let l_j = self.lj_phaserange()?;
 
         let f_1 = l_1.carrier.frequency();
         let f_j = l_j.carrier.frequency();
@@ -302,11 +515,7 @@ impl Candidate {
     /// Returns code narrow lane combination
     pub(crate) fn code_nl_combination(&self) -> Option<PseudoRangeCombination> {
         let c_1 = self.l1_pseudorange()?;
-        let c_j = self
-            .pseudo_range
-            .iter()
-            .filter(|p| p.carrier != c_1.carrier)
-            .reduce(|k, _| k)?;
+        let c_j = self.lj_pseudorange()?;
 
         let f_1 = c_1.carrier.frequency();
         let f_j = c_j.carrier.frequency();
@@ -329,17 +538,8 @@ impl Candidate {
     }
     // Form GF combination
     pub(crate) fn phase_gf_combination(&self) -> Option<PhaseCombination> {
-        let c_1 = self
-            .phase_range
-            .iter()
-            .filter(|p| matches!(p.carrier, Carrier::L1 | Carrier::E1 | Carrier::B1aB1c))
-            .reduce(|k, _| k)?;
-
-        let c_j = self
-            .phase_range
-            .iter()
-            .filter(|p| p.carrier != c_1.carrier)
-            .reduce(|k, _| k)?;
+        let c_1 = self.l1_phaserange()?;
+        let c_j = self.lj_phaserange()?;
 
         Some(PhaseCombination {
             lhs: c_j.carrier,
@@ -350,17 +550,8 @@ impl Candidate {
     }
     // Form GF combination
     pub(crate) fn code_gf_combination(&self) -> Option<PseudoRangeCombination> {
-        let c_1 = self
-            .pseudo_range
-            .iter()
-            .filter(|p| matches!(p.carrier, Carrier::L1 | Carrier::E1 | Carrier::B1aB1c))
-            .reduce(|k, _| k)?;
-
-        let c_j = self
-            .phase_range
-            .iter()
-            .filter(|p| p.carrier != c_1.carrier)
-            .reduce(|k, _| k)?;
+        let c_1 = self.l1_pseudorange()?;
+        let c_j = self.lj_pseudorange()?;
 
         Some(PseudoRangeCombination {
             lhs: c_j.carrier,
@@ -368,33 +559,131 @@ impl Candidate {
             value: c_j.value - c_1.value,
         })
     }
-    // Computes phase windup term. Self should be fully resolved, otherwse
-    // will panic.
-    pub(crate) fn windup_correction(&mut self, ref_enu: Vector3<f64>, sun: Vector3<f64>) -> f64 {
-        0.0
-        // let state = self.state.unwrap();
-        // let r_sv = state.to_ecef();
-
-        // let norm = (
-        //     (sun[0] - r_sv[0]).powi(2)
-        //     + (sun[1] - r_sv[1]).powi(2)
-        //     + (sun[2] - r_sv[2]).powi(2)
-        // ).sqrt();
+    /*
+     * Updates and returns the phase wind-up [cycles] of this vehicle, for a
+     * receiver located at `r_rx` (ECEF [m], `lat`, `lon` [rad]) whose antenna
+     * is aligned with the local North, given the vehicle attitude.
+     * Self.wind_up should hold the previous estimate of the same vehicle:
+     * wind-up is accumulated (unwrapped) across epochs.
+     * Self.state and self.attitude must be resolved.
+     */
+    pub(crate) fn windup_correction(&mut self, r_rx: Vector3<f64>, lat: f64, lon: f64) -> f64 {
+        let (r_sv, attitude) = match (self.state, self.attitude) {
+            (Some(state), Some(attitude)) => (state.position, attitude),
+            _ => return self.wind_up,
+        };
+        let k = match (r_rx - r_sv).try_normalize(0.0) {
+            Some(k) => k,
+            None => return self.wind_up,
+        };
+        // receiver dipole: x = North, y = West
+        let enu = enu_rotation(lat, lon);
+        let (x_r, y_r) = (enu.column(1).clone_owned(), -enu.column(0).clone_owned());
 
-        // let e = (r_sun - r_sv_mc ) / norm;
-        // let j = k.cross(e);
-        // let i = j.cross(k);
+        // effective dipoles
+        let d_s = attitude.x - k * k.dot(&attitude.x) - k.cross(&attitude.y);
+        let d_r = x_r - k * k.dot(&x_r) + k.cross(&y_r);
 
-        // let d_prime_norm = d_prime.norm();
-        // let d_norm = d.norm();
-        // let psi = pho * (d_prime.cross(d));
-        // let dphi = d_prime.dot(d) / d_prime.norm() / d.norm();
+        let cos = (d_s.dot(&d_r) / d_s.norm() / d_r.norm()).clamp(-1.0, 1.0);
+        let mut phi = cos.acos() / 2.0 / PI;
+        if k.dot(&d_s.cross(&d_r)) < 0.0 {
+            phi = -phi;
+        }
+        self.wind_up = phi + (self.wind_up - phi + 0.5).floor();
+        self.wind_up
+    }
+    /*
+     * Signals observed on this vehicle
+     */
+    fn carriers(&self) -> Vec<Carrier> {
+        self.pseudo_range
+            .iter()
+            .map(|pr| pr.carrier)
+            .chain(self.phase_range.iter().map(|ph| ph.carrier))
+            .unique()
+            .collect()
+    }
+    /*
+     * Resolves the satellite antenna phase center of each observed signal,
+     * from the antenna model of this vehicle, for a receiver located at `r_rx` (ECEF [m]).
+     * Self.state must be resolved (Center of Mass) and self.attitude must be modeled.
+     */
+    pub(crate) fn sv_antenna_model(&mut self, antenna: &Antenna, r_rx: Vector3<f64>) {
+        self.sv_phase_center.clear();
+        let (r_sv, attitude) = match (self.state, self.attitude) {
+            (Some(state), Some(attitude)) => (state.position, attitude),
+            _ => return,
+        };
+        let los = match (r_rx - r_sv).try_normalize(0.0) {
+            Some(los) => los,
+            None => return,
+        };
+        let nadir = los.dot(&attitude.z).clamp(-1.0, 1.0).acos().to_degrees();
+        let azimuth = los
+            .dot(&attitude.x)
+            .atan2(los.dot(&attitude.y))
+            .to_degrees();
 
-        // let n = (self.delta_phi.unwrap_or(0.0) / 2.0 / PI).round();
-        // self.delta_phi = dphi + 2.0 * n;
+        for carrier in self.carriers() {
+            if let Some(pattern) = antenna.pattern(self.sv.constellation, carrier) {
+                let pco = pattern.pco();
+                let phase_center = PhaseCenter {
+                    offset: attitude.x * pco[0] + attitude.y * pco[1] + attitude.z * pco[2],
+                    variation: pattern.pcv(nadir, azimuth),
+                };
+                debug!(
+                    "{}({}/{}): sv phase center {:?}",
+                    self.t, self.sv, carrier, phase_center
+                );
+                self.sv_phase_center.insert(carrier, phase_center);
+            }
+        }
+    }
+    /*
+     * Resolves the receiver antenna phase center of each observed signal,
+     * from the receiver antenna model, at this location (`lat`, `lon` [rad]).
+     * Antenna is assumed aligned with the local North. Self.state must be resolved.
+     */
+    pub(crate) fn rx_antenna_model(&mut self, antenna: &Antenna, lat: f64, lon: f64) {
+        self.rx_phase_center.clear();
+        let state = match self.state {
+            Some(state) => state,
+            None => return,
+        };
+        let zenith = 90.0 - state.elevation;
+        let enu = enu_rotation(lat, lon);
 
-        // self.delta_phi
-        // self.wind_up =
+        for carrier in self.carriers() {
+            if let Some(pattern) = antenna.pattern(self.sv.constellation, carrier) {
+                // North, East, Up
+                let pco = pattern.pco();
+                let phase_center = PhaseCenter {
+                    offset: enu * Vector3::new(pco[1], pco[0], pco[2]),
+                    variation: pattern.pcv(zenith, state.azimuth),
+                };
+                debug!(
+                    "{}({}/{}): rx phase center {:?}",
+                    self.t, self.sv, carrier, phase_center
+                );
+                self.rx_phase_center.insert(carrier, phase_center);
+            }
+        }
+    }
+    /*
+     * Satellite antenna phase center correction of given signal,
+     * or ionosphere free combination of two signals.
+     * Null when the antenna is not modeled.
+     */
+    pub(crate) fn sv_phase_center(&self, c_1: Carrier, c_j: Option<Carrier>) -> PhaseCenter {
+        phase_center(&self.sv_phase_center, c_1, c_j)
+    }
+    /*
+     * Receiver antenna phase center correction of given signal,
+     * or ionosphere free combination of two signals.
+     * Null when the antenna is not modeled.
+     */
+    pub(crate) fn rx_phase_center(&self, c_1: Carrier, c_j: Option<Carrier>) -> PhaseCenter {
+        phase_center(&self.rx_phase_center, c_1, c_j)
     }
     // Retains only observations with SNR >= min_snr
     pub(crate) fn min_snr_mask(&mut self, min_snr: f64) {
@@ -412,13 +701,13 @@ impl Candidate {
                 false
             }
         });
-        // self.doppler.retain(|d| {
-        //     if let Some(snr) = d.snr {
-        //         snr >= min_snr
-        //     } else {
-        //         false
-        //     }
-        // });
+        self.doppler.retain(|d| {
+            if let Some(snr) = d.snr {
+                snr >= min_snr
+            } else {
+                false
+            }
+        });
     }
     ///
     /// Computes signal transmission time, expressed as [Epoch]
@@ -474,10 +763,31 @@ impl Candidate {
     }
 }
 
+/*
+ * Phase center correction of given signal, or ionosphere free
+ * combination of two signals. Null when a signal is not modeled.
+ */
+fn phase_center(
+    models: &HashMap<Carrier, PhaseCenter>,
+    c_1: Carrier,
+    c_j: Option<Carrier>,
+) -> PhaseCenter {
+    match (models.get(&c_1), c_j) {
+        (Some(pc_1), None) => *pc_1,
+        (Some(pc_1), Some(c_j)) => match models.get(&c_j) {
+            Some(pc_j) => pc_1.if_combination(c_1.frequency(), pc_j, c_j.frequency()),
+            None => PhaseCenter::default(),
+        },
+        _ => PhaseCenter::default(),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::{PhaseCombination, PseudoRangeCombination};
-    use crate::prelude::{Candidate, Carrier, Duration, Epoch, PhaseRange, PseudoRange, SV};
+    use crate::prelude::{
+        Candidate, Carrier, Constellation, Duration, Epoch, PhaseRange, PseudoRange, SV,
+    };
     #[test]
     fn cpp_compatibility() {
         for (pr_observations, phase_observations, cpp_compatible) in [
@@ -486,6 +796,7 @@ mod test {
                     value: 1.0,
                     snr: Some(1.0),
                     carrier: Carrier::L1,
+                    code: None,
                 }],
                 vec![],
                 false,
@@ -496,11 +807,13 @@ mod test {
                         value: 1.0,
                         snr: Some(1.0),
                         carrier: Carrier::L1,
+                        code: None,
                     },
                     PseudoRange {
                         value: 2.0,
                         snr: Some(2.0),
                         carrier: Carrier::L2,
+                        code: None,
                     },
                 ],
                 vec![],
@@ -518,4 +831,112 @@ mod test {
             assert_eq!(cd.cpp_compatible(), cpp_compatible);
         }
     }
+    fn dual_candidate(constellation: Constellation, carriers: &[Carrier]) -> Candidate {
+        Candidate::new(
+            SV::new(constellation, 1),
+            Epoch::default(),
+            Duration::default(),
+            None,
+            carriers
+                .iter()
+                .map(|carrier| PseudoRange {
+                    value: 20.0E6,
+                    snr: None,
+                    carrier: *carrier,
+                    code: None,
+                })
+                .collect(),
+            carriers
+                .iter()
+                .map(|carrier| PhaseRange {
+                    value: 20.0E6,
+                    snr: None,
+                    carrier: *carrier,
+                    code: None,
+                    ambiguity: None,
+                    lli: None,
+                    lock_time: None,
+                })
+                .collect(),
+        )
+    }
+    #[test]
+    fn dual_frequency() {
+        for (constellation, carriers, dual) in [
+            (Constellation::GPS, vec![Carrier::L1, Carrier::L2], true),
+            (Constellation::GPS, vec![Carrier::L1, Carrier::L1C], false),
+            (
+                Constellation::Galileo,
+                vec![Carrier::E1, Carrier::E5A],
+                true,
+            ),
+            (
+                Constellation::Galileo,
+                vec![Carrier::E5A, Carrier::E5B],
+                false,
+            ),
+            (Constellation::BeiDou, vec![Carrier::B1I, Carrier::B3], true),
+            (
+                Constellation::BeiDou,
+                vec![Carrier::B1I, Carrier::B1aB1c],
+                false,
+            ),
+            (
+                Constellation::Glonass,
+                vec![Carrier::G1(1), Carrier::G2(1)],
+                true,
+            ),
+            (
+                Constellation::Glonass,
+                vec![Carrier::G1(1), Carrier::G1a],
+                false,
+            ),
+            (
+                Constellation::IRNSS,
+                vec![Carrier::NavicL5, Carrier::NavicS],
+                true,
+            ),
+            (
+                Constellation::IRNSS,
+                vec![Carrier::L1, Carrier::NavicL5],
+                true,
+            ),
+            (
+                Constellation::IRNSS,
+                vec![Carrier::L1, Carrier::NavicL5, Carrier::NavicS],
+                true,
+            ),
+            (Constellation::IRNSS, vec![Carrier::NavicL5], false),
+        ] {
+            let cd = dual_candidate(constellation, &carriers);
+            assert_eq!(cd.cpp_compatible(), dual, "{:?}", carriers);
+            assert_eq!(cd.ppp_compatible(), dual, "{:?}", carriers);
+            // dual frequency candidates always form the combinations
+            assert_eq!(cd.code_if_combination().is_some(), dual, "{:?}", carriers);
+        }
+        // NavIC L5 is the primary signal of L5 + S pairs only
+        for (carriers, reference, lhs) in [
+            (
+                vec![Carrier::NavicL5, Carrier::NavicS],
+                Carrier::NavicL5,
+                Carrier::NavicS,
+            ),
+            (
+                vec![Carrier::L1, Carrier::NavicL5],
+                Carrier::L1,
+                Carrier::NavicL5,
+            ),
+            (
+                vec![Carrier::L1, Carrier::NavicL5, Carrier::NavicS],
+                Carrier::L1,
+                Carrier::NavicS,
+            ),
+        ] {
+            let cd = dual_candidate(Constellation::IRNSS, &carriers);
+            let code = cd.code_if_combination().unwrap();
+            assert_eq!((code.reference, code.lhs), (reference, lhs));
+            let phase = cd.phase_gf_combination().unwrap();
+            assert_eq!((phase.reference, phase.lhs), (reference, lhs));
+        }
+    }
 }
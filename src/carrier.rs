@@ -1,16 +1,40 @@
 use nyx::cosmic::SPEED_OF_LIGHT;
+use thiserror::Error;
+
+use crate::prelude::Constellation;
+
+/// Carrier parsing error
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParsingError {
+    #[error("invalid observable \"{0}\"")]
+    InvalidObservable(String),
+    #[error("unknown constellation \"{0}\"")]
+    UnknownConstellation(char),
+    #[error("{0} does not transmit \"{1}\"")]
+    UnknownSignal(Constellation, String),
+}
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub enum Carrier {
-    /// L1 (GPS/QZSS/SBAS) same frequency as E1 and B1aB1c
+    /// L1 C/A (GPS/QZSS/SBAS) same frequency as E1 and B1aB1c
     #[default]
     L1,
-    /// L2 (GPS/QZSS)
+    /// L1C (GPS/QZSS) same frequency as L1
+    L1C,
+    /// L1 P(Y) (GPS) same frequency as L1, including semi-codeless tracking
+    L1P,
+    /// L1S (QZSS) augmentation signal, same frequency as L1
+    L1S,
+    /// L2C (GPS/QZSS)
     L2,
+    /// L2 P(Y) (GPS) same frequency as L2, including semi-codeless tracking
+    L2P,
     /// L5 (GPS/QZSS/SBAS) same frequency as E5A and B2A
     L5,
     /// L6 (GPS/QZSS) same frequency as E6
     L6,
+    /// LEX (QZSS) L6 experimental signal, same frequency as L6
+    LEX,
     /// E1 (Galileo)
     E1,
     /// E5 (Galileo) same frequency as B2
@@ -23,6 +47,8 @@ pub enum Carrier {
     E6,
     /// B1aB1c (BDS) same frequency as L1
     B1aB1c,
+    /// B1A (BDS) authorized signal, same frequency as B1aB1c
+    B1A,
     /// B1I (BDS)
     B1I,
     /// B2I/B2B (BDS) same frequency as E5b
@@ -33,26 +59,53 @@ pub enum Carrier {
     B2A,
     /// B3 (BDS)
     B3,
+    /// G1 (Glonass FDMA), on given frequency channel number (-7..=+6)
+    G1(i8),
+    /// G2 (Glonass FDMA), on given frequency channel number (-7..=+6)
+    G2(i8),
+    /// G3 (Glonass CDMA)
+    G3,
+    /// G1a (Glonass CDMA)
+    G1a,
+    /// G2a (Glonass CDMA)
+    G2a,
+    /// L5 (NavIC) same frequency as L5
+    NavicL5,
+    /// S (NavIC)
+    NavicS,
 }
 
 impl std::fmt::Display for Carrier {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             Self::L1 => write!(f, "L1"),
+            Self::L1C => write!(f, "L1C"),
+            Self::L1P => write!(f, "L1P"),
+            Self::L1S => write!(f, "L1S"),
             Self::L2 => write!(f, "L2"),
+            Self::L2P => write!(f, "L2P"),
             Self::L5 => write!(f, "L5"),
             Self::L6 => write!(f, "L6"),
+            Self::LEX => write!(f, "LEX"),
             Self::E1 => write!(f, "E1"),
             Self::E5 => write!(f, "E5"),
             Self::E5A => write!(f, "E5A"),
             Self::E5B => write!(f, "E5B"),
             Self::E6 => write!(f, "E6"),
             Self::B1I => write!(f, "B1I"),
+            Self::B1A => write!(f, "B1A"),
             Self::B1aB1c => write!(f, "B1A/B1C"),
             Self::B2iB2b => write!(f, "B2I/B2B"),
             Self::B2 => write!(f, "B2"),
             Self::B3 => write!(f, "B3"),
             Self::B2A => write!(f, "B2A"),
+            Self::G1(k) => write!(f, "G1({:+})", k),
+            Self::G2(k) => write!(f, "G2({:+})", k),
+            Self::G3 => write!(f, "G3"),
+            Self::G1a => write!(f, "G1a"),
+            Self::G2a => write!(f, "G2a"),
+            Self::NavicL5 => write!(f, "L5 (NavIC)"),
+            Self::NavicS => write!(f, "S (NavIC)"),
         }
     }
 }
@@ -60,19 +113,174 @@ impl std::fmt::Display for Carrier {
 impl Carrier {
     pub fn frequency(&self) -> f64 {
         match self {
-            Self::L1 | Self::E1 | Self::B1aB1c => 1575.42E6_f64,
-            Self::L2 => 1227.60E6_f64,
-            Self::L5 | Self::E5A | Self::B2A => 1176.45E6_f64,
+            Self::L1 | Self::L1C | Self::L1P | Self::L1S | Self::E1 | Self::B1aB1c | Self::B1A => {
+                1575.42E6_f64
+            },
+            Self::L2 | Self::L2P => 1227.60E6_f64,
+            Self::L5 | Self::E5A | Self::B2A | Self::NavicL5 => 1176.45E6_f64,
             Self::E5 | Self::B2 => 1191.795E6_f64,
-            Self::L6 | Self::E6 => 1278.750E6_f64,
+            Self::L6 | Self::LEX | Self::E6 => 1278.750E6_f64,
             Self::B3 => 1268.52E6_f64,
             Self::E5B | Self::B2iB2b => 1207.14E6_f64,
             Self::B1I => 1561.098E6_f64,
+            Self::G1(k) => 1602.0E6_f64 + *k as f64 * 562.5E3_f64,
+            Self::G2(k) => 1246.0E6_f64 + *k as f64 * 437.5E3_f64,
+            Self::G3 => 1202.025E6_f64,
+            Self::G1a => 1600.995E6_f64,
+            Self::G2a => 1248.06E6_f64,
+            Self::NavicS => 2492.028E6_f64,
         }
     }
     pub fn wavelength(&self) -> f64 {
         SPEED_OF_LIGHT / self.frequency()
     }
+    /// Returns the Glonass frequency channel number of FDMA carriers
+    pub fn fdma_channel(&self) -> Option<i8> {
+        match self {
+            Self::G1(k) | Self::G2(k) => Some(*k),
+            _ => None,
+        }
+    }
+    /// Returns a copy of this Glonass FDMA carrier, on given frequency channel.
+    /// Other carriers are not affected.
+    pub fn with_fdma_channel(&self, channel: i8) -> Self {
+        match self {
+            Self::G1(_) => Self::G1(channel),
+            Self::G2(_) => Self::G2(channel),
+            _ => *self,
+        }
+    }
+    /// Identifies the [Carrier] from a RINEX observable (like "C1C" or "L5Q"),
+    /// for given [Constellation]. Pseudo Range (C), Phase (L), Doppler (D)
+    /// and SNR (S) observables are accepted.
+    /// Glonass FDMA carriers are returned on channel 0, see [Self::with_fdma_channel].
+    pub fn from_observable(constellation: Constellation, code: &str) -> Result<Self, ParsingError> {
+        let invalid = || ParsingError::InvalidObservable(code.to_string());
+        let mut chars = code.trim().chars();
+        let (observable, band, attribute) = match (chars.next(), chars.next(), chars.next()) {
+            (Some(observable), Some(band), Some(attribute)) if chars.next().is_none() => {
+                (observable, band, attribute)
+            },
+            _ => return Err(invalid()),
+        };
+        if !matches!(observable, 'C' | 'L' | 'D' | 'S') {
+            return Err(invalid());
+        }
+        let carrier = match (constellation, band, attribute) {
+            (Constellation::GPS, '1', 'C') => Some(Self::L1),
+            (Constellation::GPS, '1', 'S' | 'L' | 'X') => Some(Self::L1C),
+            (Constellation::GPS, '1', 'P' | 'W' | 'Y' | 'M' | 'N') => Some(Self::L1P),
+            (Constellation::GPS, '2', 'C' | 'S' | 'L' | 'X') => Some(Self::L2),
+            (Constellation::GPS, '2', 'D' | 'P' | 'W' | 'Y' | 'M' | 'N') => Some(Self::L2P),
+            (Constellation::GPS, '5', 'I' | 'Q' | 'X') => Some(Self::L5),
+            (Constellation::QZSS, '1', 'C') => Some(Self::L1),
+            (Constellation::QZSS, '1', 'S' | 'L' | 'X') => Some(Self::L1C),
+            (Constellation::QZSS, '1', 'Z' | 'B') => Some(Self::L1S),
+            (Constellation::QZSS, '2', 'S' | 'L' | 'X') => Some(Self::L2),
+            (Constellation::QZSS, '5', 'I' | 'Q' | 'X' | 'D' | 'P' | 'Z') => Some(Self::L5),
+            (Constellation::QZSS, '6', 'S' | 'L' | 'X') => Some(Self::LEX),
+            (Constellation::QZSS, '6', 'E' | 'Z') => Some(Self::L6),
+            (Constellation::Galileo, '1', 'A' | 'B' | 'C' | 'X' | 'Z') => Some(Self::E1),
+            (Constellation::Galileo, '5', 'I' | 'Q' | 'X') => Some(Self::E5A),
+            (Constellation::Galileo, '7', 'I' | 'Q' | 'X') => Some(Self::E5B),
+            (Constellation::Galileo, '8', 'I' | 'Q' | 'X') => Some(Self::E5),
+            (Constellation::Galileo, '6', 'A' | 'B' | 'C' | 'X' | 'Z') => Some(Self::E6),
+            (Constellation::Glonass, '1', 'C' | 'P') => Some(Self::G1(0)),
+            (Constellation::Glonass, '2', 'C' | 'P') => Some(Self::G2(0)),
+            (Constellation::Glonass, '3', 'I' | 'Q' | 'X') => Some(Self::G3),
+            (Constellation::Glonass, '4', 'A' | 'B' | 'X') => Some(Self::G1a),
+            (Constellation::Glonass, '6', 'A' | 'B' | 'X') => Some(Self::G2a),
+            (Constellation::BeiDou, '2', 'I' | 'Q' | 'X') => Some(Self::B1I),
+            (Constellation::BeiDou, '1', 'D' | 'P' | 'X') => Some(Self::B1aB1c),
+            (Constellation::BeiDou, '1', 'S' | 'L' | 'Z') => Some(Self::B1A),
+            (Constellation::BeiDou, '5', 'D' | 'P' | 'X') => Some(Self::B2A),
+            (Constellation::BeiDou, '7', 'I' | 'Q' | 'X' | 'D' | 'P' | 'Z') => Some(Self::B2iB2b),
+            (Constellation::BeiDou, '8', 'D' | 'P' | 'X') => Some(Self::B2),
+            (Constellation::BeiDou, '6', 'I' | 'Q' | 'X' | 'A' | 'D' | 'P' | 'Z') => Some(Self::B3),
+            (Constellation::IRNSS, '1', 'D' | 'P' | 'X') => Some(Self::L1),
+            (Constellation::IRNSS, '5', 'A' | 'B' | 'C' | 'X') => Some(Self::NavicL5),
+            (Constellation::IRNSS, '9', 'A' | 'B' | 'C' | 'X') => Some(Self::NavicS),
+            (c, '1', 'C') if c.is_sbas() => Some(Self::L1),
+            (c, '5', 'I' | 'Q' | 'X') if c.is_sbas() => Some(Self::L5),
+            _ => None,
+        };
+        carrier.ok_or(ParsingError::UnknownSignal(constellation, code.to_string()))
+    }
+    /*
+     * True for signals in the L1 band, that serve as primary signal
+     * of dual frequency combinations.
+     */
+    pub(crate) fn is_l1(&self) -> bool {
+        matches!(
+            self,
+            Self::L1
+                | Self::L1C
+                | Self::L1P
+                | Self::L1S
+                | Self::E1
+                | Self::B1aB1c
+                | Self::B1A
+                | Self::B1I
+                | Self::G1(_)
+                | Self::G1a
+        )
+    }
+    /*
+     * Selection priority (lowest is preferred) among signals
+     * that may serve the same purpose: open service signals first,
+     * then the legacy secondary frequencies used by IGS products.
+     */
+    pub(crate) fn priority(&self) -> u8 {
+        match self {
+            // primary signals
+            Self::L1 | Self::E1 | Self::B1I | Self::G1(_) => 0,
+            Self::L1C | Self::B1aB1c | Self::G1a | Self::NavicL5 => 1,
+            Self::L1P => 2,
+            Self::L1S | Self::B1A => 3,
+            // secondary signals
+            Self::L2P | Self::E5A | Self::B3 | Self::G2(_) | Self::NavicS => 0,
+            Self::L2 | Self::E5B | Self::B2A | Self::G3 => 1,
+            Self::L5 | Self::E5 | Self::B2iB2b | Self::G2a => 2,
+            Self::E6 | Self::B2 | Self::L6 => 3,
+            Self::LEX => 4,
+        }
+    }
+}
+
+/*
+ * Identifies the [Constellation] from its RINEX identifier (like 'G')
+ */
+pub(crate) fn rinex_constellation(id: char) -> Result<Constellation, ParsingError> {
+    match id {
+        'G' => Ok(Constellation::GPS),
+        'R' => Ok(Constellation::Glonass),
+        'E' => Ok(Constellation::Galileo),
+        'C' => Ok(Constellation::BeiDou),
+        'J' => Ok(Constellation::QZSS),
+        'I' => Ok(Constellation::IRNSS),
+        'S' => Ok(Constellation::SBAS),
+        _ => Err(ParsingError::UnknownConstellation(id)),
+    }
+}
+
+impl std::str::FromStr for Carrier {
+    type Err = ParsingError;
+    /// Parses a RINEX observable, like "C1C" or "L5Q". When the observable is prefixed by
+    /// a RINEX constellation identifier (like "EC1C" or "E:C1C"), it is interpreted
+    /// for this [Constellation], otherwise GPS is assumed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (constellation, code) = match s.len() {
+            3 => (Constellation::GPS, s),
+            4 | 5 => {
+                let id = s.chars().next().unwrap();
+                let constellation = rinex_constellation(id)?;
+                (constellation, s[id.len_utf8()..].trim_start_matches(':'))
+            },
+            _ => return Err(ParsingError::InvalidObservable(s.to_string())),
+        };
+        Self::from_observable(constellation, code)
+    }
 }
 
 /// Signal used in [PVTSolution] resolution
@@ -99,3 +307,74 @@ impl std::fmt::Display for Signal {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Carrier, ParsingError};
+    use crate::prelude::Constellation;
+    use std::str::FromStr;
+    #[test]
+    fn fdma() {
+        assert_eq!(Carrier::G1(0).frequency(), 1602.0E6);
+        assert_eq!(Carrier::G1(-7).frequency(), 1598.0625E6);
+        assert_eq!(Carrier::G2(6).frequency(), 1248.625E6);
+        assert_eq!(Carrier::G1(1).fdma_channel(), Some(1));
+        assert_eq!(Carrier::G3.fdma_channel(), None);
+        assert!(Carrier::G1(-1).wavelength() > Carrier::G1(1).wavelength());
+        // G1/G2 ratio is channel independent
+        for k in -7..=6 {
+            let ratio = Carrier::G1(k).frequency() / Carrier::G2(k).frequency();
+            assert!((ratio - 9.0 / 7.0).abs() < 1.0E-12);
+        }
+    }
+    #[test]
+    fn rinex_observables() {
+        for (constellation, code, expected) in [
+            (Constellation::GPS, "C1C", Carrier::L1),
+            (Constellation::GPS, "C1W", Carrier::L1P),
+            (Constellation::GPS, "L1L", Carrier::L1C),
+            (Constellation::GPS, "C2L", Carrier::L2),
+            (Constellation::GPS, "L2W", Carrier::L2P),
+            (Constellation::GPS, "L5Q", Carrier::L5),
+            (Constellation::QZSS, "C1Z", Carrier::L1S),
+            (Constellation::QZSS, "L6L", Carrier::LEX),
+            (Constellation::Galileo, "C1C", Carrier::E1),
+            (Constellation::Galileo, "L5Q", Carrier::E5A),
+            (Constellation::Galileo, "D7Q", Carrier::E5B),
+            (Constellation::Galileo, "S8X", Carrier::E5),
+            (Constellation::BeiDou, "C2I", Carrier::B1I),
+            (Constellation::BeiDou, "C1P", Carrier::B1aB1c),
+            (Constellation::BeiDou, "C6I", Carrier::B3),
+            (Constellation::Glonass, "C1C", Carrier::G1(0)),
+            (Constellation::Glonass, "L3Q", Carrier::G3),
+            (Constellation::IRNSS, "C5A", Carrier::NavicL5),
+            (Constellation::IRNSS, "L9A", Carrier::NavicS),
+            (Constellation::EGNOS, "C1C", Carrier::L1),
+            (Constellation::WAAS, "L5I", Carrier::L5),
+        ] {
+            let carrier = Carrier::from_observable(constellation, code).unwrap();
+            assert_eq!(carrier, expected, "{} {}", constellation, code);
+        }
+
+        assert_eq!(Carrier::from_str("C1C"), Ok(Carrier::L1));
+        assert_eq!(Carrier::from_str("L5Q"), Ok(Carrier::L5));
+        assert_eq!(Carrier::from_str("EC1C"), Ok(Carrier::E1));
+        assert_eq!(Carrier::from_str("C:C2I"), Ok(Carrier::B1I));
+        assert_eq!(
+            Carrier::from_str("RC1C").map(|c| c.with_fdma_channel(-3)),
+            Ok(Carrier::G1(-3))
+        );
+        assert!(matches!(
+            Carrier::from_str("X1C"),
+            Err(ParsingError::InvalidObservable(_))
+        ));
+        assert!(matches!(
+            Carrier::from_str("ZC1C"),
+            Err(ParsingError::UnknownConstellation('Z'))
+        ));
+        assert!(matches!(
+            Carrier::from_str("EC2W"),
+            Err(ParsingError::UnknownSignal(Constellation::Galileo, _))
+        ));
+    }
+}
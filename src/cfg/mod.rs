@@ -5,11 +5,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     navigation::Filter,
-    prelude::{PVTSolutionType, TimeScale},
+    prelude::{Constellation, Duration, Epoch, PVTSolutionType, TimeScale},
 };
 
-use nalgebra::{base::dimension::U8, OMatrix};
-
 mod method;
 pub use method::Method;
 
@@ -44,6 +42,117 @@ pub enum Profile {
     Static,
     /// Roaming: Pedestrian (5 to 10 km/h)
     Pedestrian,
+    /// Roaming: road vehicle
+    Car,
+    /// Roaming: aircraft
+    Airplane,
+}
+
+impl Profile {
+    /*
+     * Power spectral density of the receiver (white noise) acceleration,
+     * per axis [m²/s³]. Receivers held in static do not move.
+     */
+    pub(crate) fn acceleration_psd(&self) -> Option<f64> {
+        match self {
+            Self::Static => None,
+            Self::Pedestrian => Some(1.0),
+            Self::Car => Some(10.0),
+            Self::Airplane => Some(100.0),
+        }
+    }
+}
+
+/// Broadcast offset between the system time of one [Constellation]
+/// and the reference system time (like GGTO or BGTO):
+/// offset(t) = a0 + a1 * (t - t_ref).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct SystemTimeOffset {
+    /// [Constellation] whose system time is described
+    pub constellation: Constellation,
+    /// Reference [Epoch] of this offset
+    pub t_ref: Epoch,
+    /// Offset [s] at reference [Epoch]: system time minus reference system time
+    pub a0: f64,
+    /// Offset drift [s/s]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub a1: f64,
+}
+
+impl SystemTimeOffset {
+    /*
+     * Evaluates this offset [s] at given Epoch
+     */
+    pub(crate) fn offset(&self, t: Epoch) -> f64 {
+        self.a0 + self.a1 * (t - self.t_ref).to_seconds()
+    }
+}
+
+/// Receiver clock offset to each system time, when candidates
+/// of several constellations are mixed. The receiver clock offset is always
+/// expressed against the system time of the reference [Constellation]
+/// (the one of [Config::timescale]), other systems are either
+/// estimated or compensated for.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum InterSystemBias {
+    /// One Inter System Bias state is estimated for each extra constellation.
+    #[default]
+    Estimated,
+    /// Broadcast [SystemTimeOffset]s are applied. Constellations
+    /// that are not described are still estimated.
+    Broadcast(Vec<SystemTimeOffset>),
+}
+
+/// Known Glonass code Inter Frequency Bias, on one frequency channel
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct ChannelBias {
+    /// Frequency channel number
+    pub channel: i8,
+    /// Receiver code delay [m] on this channel
+    pub bias: f64,
+}
+
+/// Glonass code Inter Frequency Biases (IFB): receiver code delays that
+/// depend on the FDMA frequency channel. They are expressed with respect to
+/// the Glonass Inter System Bias (see [InterSystemBias]).
+#[derive(Default, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum InterFrequencyBias {
+    /// IFB are estimated as a linear function of the frequency channel number:
+    /// one state is introduced when several channels contribute.
+    #[default]
+    Estimated,
+    /// Known IFB are compensated for. Channels that are not described are not biased.
+    Known(Vec<ChannelBias>),
+}
+
+/// Signal selection policy, for one [Constellation]: it makes sure
+/// that the same signals are used across epochs and across receivers,
+/// when several are tracked on the same frequency.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct SignalPriority {
+    /// [Constellation] this policy applies to
+    pub constellation: Constellation,
+    /// Tracking modes, expressed as RINEX band and attribute (like "1C", "1W" or "5Q"),
+    /// by decreasing priority. Observations whose tracking mode is not listed are not used,
+    /// observations without code information are used last.
+    pub codes: Vec<String>,
+}
+
+/*
+ * Constellation defining the system time of given constellation.
+ * Augmentation systems and QZSS are steered to GPST.
+ */
+pub(crate) fn time_system(constellation: Constellation) -> Constellation {
+    if constellation.is_sbas() || constellation == Constellation::QZSS {
+        Constellation::GPS
+    } else {
+        constellation
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -59,16 +168,64 @@ pub struct ElevationMappingFunction {
 
 impl ElevationMappingFunction {
     pub(crate) fn eval(&self, elev_sv: f64) -> f64 {
-        self.a + self.b * (elev_sv / self.c).exp()
+        self.a + self.b * (-elev_sv / self.c).exp()
+    }
+}
+
+/// Observables we may weight differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub enum Observable {
+    /// Pseudo Range (code) observations
+    PseudoRange,
+    /// Phase Range observations
+    PhaseRange,
+}
+
+/// Observation noise model
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct NoiseModel {
+    /// [Constellation] this model applies to.
+    /// Applies to all constellations when not defined.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub constellation: Option<Constellation>,
+    /// [Observable] this model applies to
+    pub observable: Observable,
+    /// Elevation dependent standard deviation [m]: a + b * e-elev/c,
+    /// with elev in degrees.
+    pub elevation: ElevationMappingFunction,
+    /// SNR dependent term: adds snr_factor * 10^(-SNR/10) [m²] to the variance,
+    /// with SNR in dB-Hz. Observations without SNR information are not affected.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub snr_factor: Option<f64>,
+}
+
+impl NoiseModel {
+    /*
+     * Observation variance [m²] for given elevation [°] and SNR [dB-Hz]
+     */
+    pub(crate) fn variance(&self, elevation: f64, snr: Option<f64>) -> f64 {
+        let mut variance = self.elevation.eval(elevation).powi(2);
+        if let (Some(factor), Some(snr)) = (self.snr_factor, snr) {
+            variance += factor * 10.0_f64.powf(-snr / 10.0);
+        }
+        variance
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 pub enum WeightMatrix {
-    /// a + b e-elev/c
+    /// a + b e-elev/c, applied to all observations
     MappingFunction(ElevationMappingFunction),
-    /// Advanced measurement noise covariance matrix
+    /// [NoiseModel]s, per [Constellation] and [Observable].
+    /// The most specific model applies, observations that are not described
+    /// have unit variance.
+    NoiseModels(Vec<NoiseModel>),
+    /// Advanced measurement noise covariance matrix: observation variances
+    /// are provided by the user, on each [crate::prelude::Candidate].
+    /// Observations that are not described have unit variance.
     Covar,
 }
 
@@ -84,6 +241,10 @@ fn default_smoothing() -> bool {
     false
 }
 
+fn default_smoothing_window() -> usize {
+    100
+}
+
 fn default_sv_clock() -> bool {
     true
 }
@@ -116,6 +277,10 @@ fn default_phase_windup() -> bool {
     false
 }
 
+fn default_solid_tides() -> bool {
+    false
+}
+
 fn default_postfit_kf() -> bool {
     false
 }
@@ -132,9 +297,19 @@ fn default_weight_matrix() -> Option<WeightMatrix> {
 }
 
 fn default_filter_opts() -> Option<FilterOpts> {
-    Some(FilterOpts {
-        weight_matrix: default_weight_matrix(),
-    })
+    Some(FilterOpts::default())
+}
+
+fn default_clock_offset_psd() -> f64 {
+    1.0
+}
+
+fn default_clock_drift_psd() -> f64 {
+    0.1
+}
+
+fn default_tropo_psd() -> f64 {
+    1.0E-8
 }
 
 fn default_gdop_threshold() -> Option<f64> {
@@ -145,6 +320,106 @@ fn default_tdop_threshold() -> Option<f64> {
     None
 }
 
+fn default_innovation_threshold() -> Option<f64> {
+    None
+}
+
+fn default_raim_pfa() -> f64 {
+    1.0E-3
+}
+
+fn default_raim_sigma() -> f64 {
+    1.0
+}
+
+fn default_raim_exclusions() -> usize {
+    1
+}
+
+fn default_gf_window() -> Duration {
+    Duration::from_seconds(1000.0)
+}
+
+fn default_slip_threshold() -> f64 {
+    5.0
+}
+
+fn default_mw_slip_threshold() -> f64 {
+    4.0
+}
+
+fn default_doppler_slip_threshold() -> f64 {
+    5.0
+}
+
+fn default_gap_tolerance() -> Duration {
+    Duration::from_seconds(120.0)
+}
+
+fn default_min_samples() -> usize {
+    10
+}
+
+fn default_wl_max_fraction() -> f64 {
+    0.25
+}
+
+fn default_wl_max_sigma() -> f64 {
+    0.15
+}
+
+fn default_ratio_threshold() -> f64 {
+    3.0
+}
+
+fn default_success_rate() -> f64 {
+    0.999
+}
+
+fn default_partial_fixing() -> bool {
+    true
+}
+
+fn default_min_partial_subset() -> usize {
+    4
+}
+
+fn default_max_iterations() -> usize {
+    10
+}
+
+fn default_convergence_threshold() -> f64 {
+    1.0E-3
+}
+
+fn default_phmi_vert() -> f64 {
+    9.8E-8
+}
+
+fn default_phmi_horz() -> f64 {
+    2.0E-9
+}
+
+fn default_pfa_vert() -> f64 {
+    3.9E-6
+}
+
+fn default_pfa_horz() -> f64 {
+    9.0E-8
+}
+
+fn default_p_sat() -> f64 {
+    1.0E-5
+}
+
+fn default_sigma_user() -> f64 {
+    1.0
+}
+
+fn default_sigma_tropo() -> f64 {
+    0.12
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 /// System Internal Delay as defined by BIPM in
@@ -170,6 +445,26 @@ pub struct SolverOpts {
     /// TDOP threshold to invalidate ongoing TDOP
     #[cfg_attr(feature = "serde", serde(default = "default_tdop_threshold"))]
     pub tdop_threshold: Option<f64>,
+    /// Maximal position innovation [m] of filters that have memory (see [Filter]):
+    /// the position correction compared to the predicted state.
+    /// Solutions exceeding this threshold are invalidated.
+    #[cfg_attr(feature = "serde", serde(default = "default_innovation_threshold"))]
+    pub innovation_threshold: Option<f64>,
+    /// Receiver Autonomous Integrity Monitoring. Disabled when not defined.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub raim: Option<RaimOpts>,
+    /// Integrity risk allocation, used to compute the Protection Levels
+    /// of each solution. Protection Levels are not computed when not defined.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub integrity: Option<IntegrityOpts>,
+    /// Maximal number of Gauss-Newton iterations (re-linearization
+    /// around the latest position estimate) of the LSQ [Filter], per epoch.
+    #[cfg_attr(feature = "serde", serde(default = "default_max_iterations"))]
+    pub max_iterations: usize,
+    /// Gauss-Newton iterations stop once the position correction norm [m]
+    /// falls below this threshold.
+    #[cfg_attr(feature = "serde", serde(default = "default_convergence_threshold"))]
+    pub convergence_threshold: f64,
     /// Filter to use
     #[cfg_attr(feature = "serde", serde(default))]
     pub filter: Filter,
@@ -182,34 +477,263 @@ pub struct SolverOpts {
     pub postfit_kf: bool,
 }
 
-#[derive(Default, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Deserialize))]
 pub struct FilterOpts {
     /// Weight Matrix
     #[cfg_attr(feature = "serde", serde(default = "default_weight_matrix"))]
     pub weight_matrix: Option<WeightMatrix>,
+    /// Receiver clock white frequency noise [m²/s],
+    /// process noise of the clock offset in the [Filter::Kalman].
+    #[cfg_attr(feature = "serde", serde(default = "default_clock_offset_psd"))]
+    pub clock_offset_psd: f64,
+    /// Receiver clock random walk frequency noise [m²/s³],
+    /// process noise of the clock drift in the [Filter::Kalman].
+    #[cfg_attr(feature = "serde", serde(default = "default_clock_drift_psd"))]
+    pub clock_drift_psd: f64,
+    /// Zenith wet delay random walk noise [m²/s],
+    /// process noise of the troposphere state in the [Filter::Kalman].
+    #[cfg_attr(feature = "serde", serde(default = "default_tropo_psd"))]
+    pub tropo_psd: f64,
+}
+
+impl Default for FilterOpts {
+    fn default() -> Self {
+        Self {
+            weight_matrix: default_weight_matrix(),
+            clock_offset_psd: default_clock_offset_psd(),
+            clock_drift_psd: default_clock_drift_psd(),
+            tropo_psd: default_tropo_psd(),
+        }
+    }
+}
+
+/// Receiver Autonomous Integrity Monitoring (RAIM) options.
+/// A chi-square test is performed on the post-fit residuals (global test).
+/// On failure, the SV with the largest normalized residual is excluded
+/// and the solution is resolved again (Fault Detection and Exclusion).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct RaimOpts {
+    /// Probability of false alarm of the global test
+    #[cfg_attr(feature = "serde", serde(default = "default_raim_pfa"))]
+    pub p_fa: f64,
+    /// A priori standard deviation of observations of unit weight [m]
+    #[cfg_attr(feature = "serde", serde(default = "default_raim_sigma"))]
+    pub sigma: f64,
+    /// Maximal number of SV we may exclude, per epoch
+    #[cfg_attr(feature = "serde", serde(default = "default_raim_exclusions"))]
+    pub max_exclusions: usize,
+}
+
+impl Default for RaimOpts {
+    fn default() -> Self {
+        Self {
+            p_fa: default_raim_pfa(),
+            sigma: default_raim_sigma(),
+            max_exclusions: default_raim_exclusions(),
+        }
+    }
+}
+
+/// Phase ambiguity tracking and resolution options.
+/// Phase tracking is interrupted on cycle slips (see [crate::prelude::CycleSlipDetector]).
+/// Ambiguities are resolved in cascade: the wide lane ambiguity is fixed
+/// first, from the Melbourne-Wübbena combination averaged over the phase tracking arc,
+/// then the narrow lane ambiguity. Float ambiguities estimated
+/// by the [Filter::Kalman] in [Method::PPP] are fixed to integers
+/// with the LAMBDA method, when the fixed candidate is validated.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct AmbiguityOpts {
+    /// Duration of the Geometry Free combination moving window,
+    /// used in cycle slip detection.
+    #[cfg_attr(feature = "serde", serde(default = "default_gf_window"))]
+    pub gf_window: Duration,
+    /// Cycle slip is declared when the Geometry Free combination
+    /// departs from its prediction by more than this value [m].
+    #[cfg_attr(feature = "serde", serde(default = "default_slip_threshold"))]
+    pub slip_threshold: f64,
+    /// Cycle slip is declared when the Melbourne-Wübbena combination
+    /// departs from its average over the phase tracking arc by more than this value [cycles].
+    #[cfg_attr(feature = "serde", serde(default = "default_mw_slip_threshold"))]
+    pub mw_slip_threshold: f64,
+    /// Cycle slip is declared when the phase variation departs from
+    /// the variation predicted by the Doppler shift by more than this value [cycles].
+    #[cfg_attr(feature = "serde", serde(default = "default_doppler_slip_threshold"))]
+    pub doppler_slip_threshold: f64,
+    /// Maximal data gap tolerated before the phase tracking is reset.
+    #[cfg_attr(feature = "serde", serde(default = "default_gap_tolerance"))]
+    pub gap_tolerance: Duration,
+    /// Minimal number of samples averaged, before the wide lane
+    /// then the narrow lane ambiguities may be resolved.
+    #[cfg_attr(feature = "serde", serde(default = "default_min_samples"))]
+    pub min_samples: usize,
+    /// Maximal distance [cycles] between the averaged wide lane ambiguity
+    /// and its nearest integer, for the wide lane ambiguity to be fixed.
+    #[cfg_attr(feature = "serde", serde(default = "default_wl_max_fraction"))]
+    pub wl_max_fraction: f64,
+    /// Maximal standard deviation [cycles] of the averaged wide lane ambiguity,
+    /// for the wide lane ambiguity to be fixed.
+    #[cfg_attr(feature = "serde", serde(default = "default_wl_max_sigma"))]
+    pub wl_max_sigma: f64,
+    /// Minimal ratio between the squared norms of the second best
+    /// and best integer candidates, for the best candidate to be accepted.
+    #[cfg_attr(feature = "serde", serde(default = "default_ratio_threshold"))]
+    pub ratio_threshold: f64,
+    /// Minimal bootstrapped success rate of the decorrelated ambiguities,
+    /// for the best candidate to be accepted.
+    #[cfg_attr(feature = "serde", serde(default = "default_success_rate"))]
+    pub min_success_rate: f64,
+    /// Partial ambiguity resolution: when the complete set of ambiguities
+    /// fails validation, the lowest elevation ambiguities are discarded one
+    /// at a time, until a subset passes validation.
+    #[cfg_attr(feature = "serde", serde(default = "default_partial_fixing"))]
+    pub partial_fixing: bool,
+    /// Minimal number of ambiguities in a partially fixed subset
+    #[cfg_attr(feature = "serde", serde(default = "default_min_partial_subset"))]
+    pub min_partial_subset: usize,
+}
+
+impl Default for AmbiguityOpts {
+    fn default() -> Self {
+        Self {
+            gf_window: default_gf_window(),
+            slip_threshold: default_slip_threshold(),
+            mw_slip_threshold: default_mw_slip_threshold(),
+            doppler_slip_threshold: default_doppler_slip_threshold(),
+            gap_tolerance: default_gap_tolerance(),
+            min_samples: default_min_samples(),
+            wl_max_fraction: default_wl_max_fraction(),
+            wl_max_sigma: default_wl_max_sigma(),
+            ratio_threshold: default_ratio_threshold(),
+            min_success_rate: default_success_rate(),
+            partial_fixing: default_partial_fixing(),
+            min_partial_subset: default_min_partial_subset(),
+        }
+    }
+}
+
+/// Ranging error model of one [Constellation], as broadcasted
+/// in the Integrity Support Message (ISM).
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct RangeErrorModel {
+    /// [Constellation] this model applies to
+    pub constellation: Constellation,
+    /// User Range Accuracy [m]: standard deviation used for integrity
+    pub ura: f64,
+    /// User Range Error [m]: standard deviation used for accuracy and continuity
+    pub ure: f64,
+    /// Maximal nominal bias [m], used for integrity
+    pub b_nom: f64,
+}
+
+impl RangeErrorModel {
+    /// Model used for Constellations that are not described
+    fn fallback(constellation: Constellation) -> Self {
+        Self {
+            constellation,
+            ura: 1.0,
+            ure: 2.0 / 3.0,
+            b_nom: 0.75,
+        }
+    }
+}
+
+/// Integrity risk allocation, used to compute the Advanced RAIM
+/// Horizontal and Vertical Protection Levels (HPL/VPL).
+/// Only single satellite faults are considered.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+pub struct IntegrityOpts {
+    /// Vertical integrity risk budget
+    #[cfg_attr(feature = "serde", serde(default = "default_phmi_vert"))]
+    pub phmi_vert: f64,
+    /// Horizontal integrity risk budget
+    #[cfg_attr(feature = "serde", serde(default = "default_phmi_horz"))]
+    pub phmi_horz: f64,
+    /// Vertical continuity (false alarm) budget
+    #[cfg_attr(feature = "serde", serde(default = "default_pfa_vert"))]
+    pub p_fa_vert: f64,
+    /// Horizontal continuity (false alarm) budget
+    #[cfg_attr(feature = "serde", serde(default = "default_pfa_horz"))]
+    pub p_fa_horz: f64,
+    /// A priori probability of a single satellite fault
+    #[cfg_attr(feature = "serde", serde(default = "default_p_sat"))]
+    pub p_sat: f64,
+    /// Receiver noise and multipath standard deviation [m]
+    #[cfg_attr(feature = "serde", serde(default = "default_sigma_user"))]
+    pub sigma_user: f64,
+    /// Residual zenith troposphere delay standard deviation [m],
+    /// mapped to each SV elevation
+    #[cfg_attr(feature = "serde", serde(default = "default_sigma_tropo"))]
+    pub sigma_tropo: f64,
+    /// Ranging error model, per [Constellation]. Constellations that
+    /// are not described use a 1m URA and 0.75m nominal bias.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub range_errors: Vec<RangeErrorModel>,
+}
+
+impl Default for IntegrityOpts {
+    fn default() -> Self {
+        Self {
+            phmi_vert: default_phmi_vert(),
+            phmi_horz: default_phmi_horz(),
+            p_fa_vert: default_pfa_vert(),
+            p_fa_horz: default_pfa_horz(),
+            p_sat: default_p_sat(),
+            sigma_user: default_sigma_user(),
+            sigma_tropo: default_sigma_tropo(),
+            range_errors: Vec::new(),
+        }
+    }
+}
+
+impl IntegrityOpts {
+    /// Returns [RangeErrorModel] to use for this [Constellation]
+    pub(crate) fn range_error(&self, constellation: Constellation) -> RangeErrorModel {
+        self.range_errors
+            .iter()
+            .find(|model| model.constellation == constellation)
+            .cloned()
+            .unwrap_or(RangeErrorModel::fallback(constellation))
+    }
 }
 
 impl SolverOpts {
     /*
-     * form the weight matrix to be used in the solving process
+     * Variance [m²] of one observation, used to form the weight matrix.
+     * - elevation: SV elevation [°]
+     * - snr: observation SNR [dB-Hz]
+     * - user: variance provided by the user [m²]
      */
-    pub(crate) fn weight_matrix(&self) -> OMatrix<f64, U8, U8> {
-        let mat = OMatrix::<f64, U8, U8>::identity();
-        if let Some(opts) = &self.filter_opts {
-            match &opts.weight_matrix {
-                Some(WeightMatrix::Covar) => panic!("not implemented yet"),
-                Some(WeightMatrix::MappingFunction(_)) => panic!("mapf: not implemented yet"),
-                //                Some(WeightMatrix::MappingFunction(mapf)) => {
-                //                    for i in 0..8 {
-                //                        let sigma = mapf.a + mapf.b * ((-sv_elev[i]) / mapf.c).exp();
-                //                        mat[(i, i)] = 1.0 / sigma.powi(2);
-                //                    }
-                //                },
-                None => {},
-            }
+    pub(crate) fn observation_variance(
+        &self,
+        constellation: Constellation,
+        observable: Observable,
+        elevation: f64,
+        snr: Option<f64>,
+        user: Option<f64>,
+    ) -> f64 {
+        let weight_matrix = match &self.filter_opts {
+            Some(opts) => &opts.weight_matrix,
+            None => return 1.0,
+        };
+        match weight_matrix {
+            Some(WeightMatrix::MappingFunction(mapf)) => mapf.eval(elevation).powi(2),
+            Some(WeightMatrix::NoiseModels(models)) => models
+                .iter()
+                .filter(|model| {
+                    model.observable == observable
+                        && model.constellation.unwrap_or(constellation) == constellation
+                })
+                .max_by_key(|model| model.constellation.is_some())
+                .map(|model| model.variance(elevation, snr))
+                .unwrap_or(1.0),
+            Some(WeightMatrix::Covar) => user.unwrap_or(1.0),
+            None => 1.0,
         }
-        mat
     }
 }
 
@@ -245,6 +769,10 @@ pub struct Modeling {
     /// strategies that use raw phase like [Method::PPP].
     #[cfg_attr(feature = "serde", serde(default))]
     pub phase_windup: bool,
+    /// Compensate for the solid Earth tides displacement of the station (+/- 30 cm).
+    /// This only matters to static precise positioning, like [Method::PPP].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub solid_tides: bool,
 }
 
 impl Default for Modeling {
@@ -256,6 +784,7 @@ impl Default for Modeling {
             sv_total_group_delay: default_sv_tgd(),
             earth_rotation: default_earth_rot(),
             phase_windup: default_phase_windup(),
+            solid_tides: default_solid_tides(),
             relativistic_clock_bias: default_relativistic_clock_bias(),
             relativistic_path_range: default_relativistic_path_range(),
         }
@@ -284,19 +813,45 @@ pub struct Config {
     /// Fixed altitude: reduces the need of 4 to 3 SV to obtain 3D solutions.
     #[cfg_attr(feature = "serde", serde(default))]
     pub fixed_altitude: Option<f64>,
-    /// Pseudo Range smoothing. Use this to improve solutions accuracy.
-    /// This applies to all positioning strategies.
+    /// Pseudo Range smoothing by Phase Range observations (Hatch filter).
+    /// Use this to improve solutions accuracy. This applies to [Method::SPP]
+    /// and [Method::CPP] and requires Phase Range observations on the same signal.
+    /// When a second frequency is observed, the divergence free
+    /// combination is used. Smoothing restarts on each cycle slip.
     #[cfg_attr(feature = "serde", serde(default = "default_smoothing"))]
     pub code_smoothing: bool,
+    /// Smoothing window length, in number of samples (see [Self::code_smoothing]).
+    #[cfg_attr(feature = "serde", serde(default = "default_smoothing_window"))]
+    pub smoothing_window: usize,
     /// Internal delays to compensate for (total summation, in [s]).
     #[cfg_attr(feature = "serde", serde(default))]
     pub int_delay: Vec<InternalDelay>,
-    /// Antenna Reference Point (ARP) expressed as ENU offset [m]
+    /// Antenna Reference Point (ARP) eccentricity, relative to the marker,
+    /// expressed as (East, North, Up) offsets [m]. Solutions refer to the marker.
     #[cfg_attr(feature = "serde", serde(default))]
     pub arp_enu: Option<(f64, f64, f64)>,
+    /// Receiver antenna type, including the radome code (like "TRM59800.00     NONE").
+    /// Its phase center offsets (relative to the ARP) and variations are compensated for,
+    /// when described in the ANTEX file provided to the solver.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rx_antenna: Option<String>,
     /// Solver customization
     #[cfg_attr(feature = "serde", serde(default))]
     pub solver: SolverOpts,
+    /// Describes how we deal with the system time of each constellation,
+    /// when constellations are mixed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub inter_system_bias: InterSystemBias,
+    /// Describes how we deal with Glonass code Inter Frequency Biases.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub glonass_ifb: InterFrequencyBias,
+    /// Signal selection policies, per [Constellation]. When no policy is defined,
+    /// open service signals are preferred.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub signal_priority: Vec<SignalPriority>,
+    /// Phase ambiguity tracking and resolution options
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ambiguity: AmbiguityOpts,
     /// Time Reference Delay. According to BIPM ""GPS Receivers Accurate Time Comparison""
     /// this is the time delay between the receiver external reference clock
     /// and the internal sampling clock. This is typically needed in
@@ -332,6 +887,39 @@ pub struct Config {
 }
 
 impl Config {
+    /*
+     * True when float phase ambiguities are estimated by the navigation filter
+     */
+    pub(crate) fn float_ambiguities(&self) -> bool {
+        self.method == Method::PPP && self.solver.filter == Filter::Kalman
+    }
+    /*
+     * Constellation the receiver clock offset refers to
+     */
+    pub(crate) fn reference_constellation(&self) -> Constellation {
+        match self.timescale {
+            TimeScale::GST => Constellation::Galileo,
+            TimeScale::BDT => Constellation::BeiDou,
+            _ => Constellation::GPS,
+        }
+    }
+    /*
+     * Broadcast offset [s] between the system time of this constellation
+     * and the reference system time, if it is known.
+     */
+    pub(crate) fn system_time_offset(&self, constellation: Constellation, t: Epoch) -> Option<f64> {
+        let system = time_system(constellation);
+        if system == self.reference_constellation() {
+            return Some(0.0);
+        }
+        match &self.inter_system_bias {
+            InterSystemBias::Estimated => None,
+            InterSystemBias::Broadcast(offsets) => offsets
+                .iter()
+                .find(|offset| time_system(offset.constellation) == system)
+                .map(|offset| offset.offset(t)),
+        }
+    }
     /// Returns a basic [Config] that is consistent with given Positioning [Method]
     /// and a static GNSS receiver.
     pub fn static_preset(method: Method) -> Self {
@@ -341,11 +929,17 @@ impl Config {
                 profile: Profile::Static,
                 sol_type: PVTSolutionType::default(),
                 arp_enu: None,
+                rx_antenna: None,
                 fixed_altitude: None,
                 timescale: default_timescale(),
                 interp_order: default_interp(),
                 code_smoothing: default_smoothing(),
+                smoothing_window: default_smoothing_window(),
                 min_snr: None,
+                inter_system_bias: InterSystemBias::default(),
+                glonass_ifb: InterFrequencyBias::default(),
+                signal_priority: Vec::new(),
+                ambiguity: AmbiguityOpts::default(),
                 min_sv_elev: Some(7.5),
                 min_sv_azim: None,
                 max_sv_azim: None,
@@ -357,6 +951,11 @@ impl Config {
                     filter: Filter::LSQ,
                     gdop_threshold: default_gdop_threshold(),
                     tdop_threshold: default_tdop_threshold(),
+                    innovation_threshold: default_innovation_threshold(),
+                    raim: None,
+                    integrity: None,
+                    max_iterations: default_max_iterations(),
+                    convergence_threshold: default_convergence_threshold(),
                     filter_opts: default_filter_opts(),
                     postfit_kf: default_postfit_kf(),
                 },
@@ -366,11 +965,17 @@ impl Config {
                 profile: Profile::Static,
                 sol_type: PVTSolutionType::default(),
                 arp_enu: None,
+                rx_antenna: None,
                 fixed_altitude: None,
                 timescale: default_timescale(),
                 interp_order: default_interp(),
                 code_smoothing: default_smoothing(),
+                smoothing_window: default_smoothing_window(),
                 min_snr: None,
+                inter_system_bias: InterSystemBias::default(),
+                glonass_ifb: InterFrequencyBias::default(),
+                signal_priority: Vec::new(),
+                ambiguity: AmbiguityOpts::default(),
                 min_sv_elev: Some(7.5),
                 min_sv_azim: None,
                 max_sv_azim: None,
@@ -382,6 +987,11 @@ impl Config {
                     filter: Filter::LSQ,
                     gdop_threshold: default_gdop_threshold(),
                     tdop_threshold: default_tdop_threshold(),
+                    innovation_threshold: default_innovation_threshold(),
+                    raim: None,
+                    integrity: None,
+                    max_iterations: default_max_iterations(),
+                    convergence_threshold: default_convergence_threshold(),
                     filter_opts: default_filter_opts(),
                     postfit_kf: default_postfit_kf(),
                 },
@@ -391,11 +1001,17 @@ impl Config {
                 profile: Profile::Static,
                 sol_type: PVTSolutionType::default(),
                 arp_enu: None,
+                rx_antenna: None,
                 fixed_altitude: None,
                 timescale: default_timescale(),
                 interp_order: default_interp(),
                 code_smoothing: default_smoothing(),
+                smoothing_window: default_smoothing_window(),
                 min_snr: None,
+                inter_system_bias: InterSystemBias::default(),
+                glonass_ifb: InterFrequencyBias::default(),
+                signal_priority: Vec::new(),
+                ambiguity: AmbiguityOpts::default(),
                 min_sv_elev: Some(7.5),
                 min_sv_azim: None,
                 max_sv_azim: None,
@@ -407,6 +1023,11 @@ impl Config {
                     filter: Filter::LSQ,
                     gdop_threshold: default_gdop_threshold(),
                     tdop_threshold: default_tdop_threshold(),
+                    innovation_threshold: default_innovation_threshold(),
+                    raim: None,
+                    integrity: None,
+                    max_iterations: default_max_iterations(),
+                    convergence_threshold: default_convergence_threshold(),
                     filter_opts: default_filter_opts(),
                     postfit_kf: default_postfit_kf(),
                 },
@@ -414,3 +1035,100 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        ElevationMappingFunction, FilterOpts, NoiseModel, Observable, SolverOpts, WeightMatrix,
+    };
+    use crate::prelude::Constellation;
+    fn opts(weight_matrix: WeightMatrix) -> SolverOpts {
+        SolverOpts {
+            filter_opts: Some(FilterOpts {
+                weight_matrix: Some(weight_matrix),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+    #[test]
+    fn observation_variance() {
+        let mapf = ElevationMappingFunction {
+            a: 0.3,
+            b: 3.0,
+            c: 10.0,
+        };
+        let opts_mapf = opts(WeightMatrix::MappingFunction(mapf.clone()));
+        let low = opts_mapf.observation_variance(
+            Constellation::GPS,
+            Observable::PseudoRange,
+            10.0,
+            None,
+            None,
+        );
+        let high = opts_mapf.observation_variance(
+            Constellation::GPS,
+            Observable::PseudoRange,
+            80.0,
+            None,
+            None,
+        );
+        assert!(low > high, "low elevation should be deweighted");
+        assert!((high - mapf.eval(80.0).powi(2)).abs() < 1.0E-12);
+
+        let opts_models = opts(WeightMatrix::NoiseModels(vec![
+            NoiseModel {
+                constellation: None,
+                observable: Observable::PseudoRange,
+                elevation: mapf.clone(),
+                snr_factor: Some(1.0E4),
+            },
+            NoiseModel {
+                constellation: Some(Constellation::Galileo),
+                observable: Observable::PseudoRange,
+                elevation: ElevationMappingFunction {
+                    a: 0.1,
+                    b: 0.0,
+                    c: 1.0,
+                },
+                snr_factor: None,
+            },
+        ]));
+        let gps = opts_models.observation_variance(
+            Constellation::GPS,
+            Observable::PseudoRange,
+            80.0,
+            Some(40.0),
+            None,
+        );
+        assert!((gps - (mapf.eval(80.0).powi(2) + 1.0)).abs() < 1.0E-9);
+        let gal = opts_models.observation_variance(
+            Constellation::Galileo,
+            Observable::PseudoRange,
+            80.0,
+            Some(40.0),
+            None,
+        );
+        assert!((gal - 0.01).abs() < 1.0E-12, "specific model should apply");
+        let phase = opts_models.observation_variance(
+            Constellation::GPS,
+            Observable::PhaseRange,
+            80.0,
+            None,
+            None,
+        );
+        assert_eq!(phase, 1.0);
+
+        let opts_covar = opts(WeightMatrix::Covar);
+        for (user, expected) in [(Some(4.0), 4.0), (None, 1.0)] {
+            let var = opts_covar.observation_variance(
+                Constellation::GPS,
+                Observable::PseudoRange,
+                45.0,
+                None,
+                user,
+            );
+            assert_eq!(var, expected);
+        }
+    }
+}
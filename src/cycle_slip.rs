@@ -0,0 +1,305 @@
+//! Cycle slip detection
+use crate::{
+    ambiguity::Averager,
+    cfg::AmbiguityOpts,
+    prelude::{Candidate, Carrier, Duration, Epoch, PhaseRange, SV},
+};
+use log::{debug, warn};
+use nyx::cosmic::SPEED_OF_LIGHT;
+use polyfit_rs::polyfit_rs::polyfit;
+use std::collections::HashMap;
+
+/// Reason why a cycle slip was declared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlipCause {
+    /// Data gap larger than tolerated, or first sight of the vehicle
+    DataGap,
+    /// Loss of lock reported by the receiver
+    LossOfLock,
+    /// Jump of the Geometry Free phase combination
+    GeometryFree,
+    /// Jump of the Melbourne-Wübbena combination
+    MelbourneWubbena,
+    /// Phase variation not consistent with the Doppler shift
+    Doppler,
+}
+
+/// Cycle slip event: phase tracking of this [SV] was interrupted,
+/// phase ambiguities and smoothed observations must be reset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CycleSlip {
+    /// [Epoch] of detection
+    pub t: Epoch,
+    /// [SV] whose phase tracking was interrupted
+    pub sv: SV,
+    /// Reason why the cycle slip was declared
+    pub cause: SlipCause,
+}
+
+/// GF combination moving window
+struct Buffer {
+    window: Duration,
+    inner: Vec<(Epoch, f64)>,
+}
+
+impl Buffer {
+    /// Allocates a new Buffer
+    fn malloc(capacity: usize, window: Duration) -> Self {
+        Self {
+            window,
+            inner: Vec::with_capacity(capacity),
+        }
+    }
+    /// Push data into self
+    fn push(&mut self, t: Epoch, y: f64) {
+        self.inner.push((t, y));
+        let t0 = self.inner[0].0;
+        if (t - t0) > self.window {
+            self.inner.remove(0);
+        }
+    }
+    /// Resets self
+    fn reset(&mut self) {
+        self.inner.clear();
+    }
+    /// Performs polyfit (nth order) over self, time being
+    /// expressed in seconds since the first sample of self
+    fn polyfit(&self, order: usize) -> Option<(Epoch, Vec<f64>)> {
+        if self.inner.len() > order {
+            let t0 = self.inner[0].0;
+            let x_s = self
+                .inner
+                .iter()
+                .map(|(k, _)| (*k - t0).to_seconds())
+                .collect::<Vec<f64>>();
+            let y_s = self.inner.iter().map(|(_, v)| *v).collect::<Vec<f64>>();
+            match polyfit(&x_s, &y_s, order) {
+                Ok(fit) => Some((t0, fit)),
+                Err(e) => {
+                    warn!("polyfit error: {}", e);
+                    None
+                },
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Phase tracking of one SV
+struct SVTracker {
+    /// last seen [Epoch]
+    last_seen: Epoch,
+    /// GF moving window
+    gf_buffer: Buffer,
+    /// MW average [cycles]
+    mw_tracker: Averager,
+    /// Last phase range [m] and Doppler shift [Hz], per signal
+    phases: HashMap<Carrier, (f64, Option<f64>)>,
+}
+
+impl SVTracker {
+    fn new(last_seen: Epoch, opts: &AmbiguityOpts) -> Self {
+        Self {
+            last_seen,
+            gf_buffer: Buffer::malloc(128, opts.gf_window),
+            mw_tracker: Averager::new(),
+            phases: HashMap::with_capacity(4),
+        }
+    }
+    fn reset(&mut self) {
+        self.gf_buffer.reset();
+        self.mw_tracker.reset();
+        self.phases.clear();
+    }
+}
+
+/// [CycleSlipDetector] monitors the phase tracking of each SV and declares
+/// cycle slips, combining a data gap test, a Geometry Free prediction test,
+/// a Melbourne-Wübbena test and a Doppler versus phase rate test.
+pub struct CycleSlipDetector {
+    /// Detection options
+    opts: AmbiguityOpts,
+    /// [SV] trackers
+    sv_trackers: HashMap<SV, SVTracker>,
+}
+
+impl CycleSlipDetector {
+    /// Builds new [CycleSlipDetector] with given [AmbiguityOpts].
+    pub fn new(opts: &AmbiguityOpts) -> Self {
+        Self {
+            opts: opts.clone(),
+            sv_trackers: HashMap::with_capacity(8),
+        }
+    }
+    /// Processes new observations, returns the declared [CycleSlip]s.
+    /// First sight of a vehicle is reported as a [SlipCause::DataGap].
+    pub fn detect(&mut self, pool: &[Candidate]) -> Vec<CycleSlip> {
+        let mut slips = Vec::<CycleSlip>::new();
+        let clock_jump = self.clock_jump(pool);
+        for cd in pool {
+            if cd.phase_range.is_empty() {
+                continue;
+            }
+            let first_sight = !self.sv_trackers.contains_key(&cd.sv);
+            let tracker = self
+                .sv_trackers
+                .entry(cd.sv)
+                .or_insert_with(|| SVTracker::new(cd.t, &self.opts));
+
+            let cause = if first_sight {
+                Some(SlipCause::DataGap)
+            } else {
+                Self::test(cd, tracker, &self.opts, clock_jump)
+            };
+            if let Some(cause) = cause {
+                debug!("{}({}): cycle slip declared ({:?})", cd.t, cd.sv, cause);
+                slips.push(CycleSlip {
+                    t: cd.t,
+                    sv: cd.sv,
+                    cause,
+                });
+                tracker.reset();
+            }
+
+            // update
+            tracker.last_seen = cd.t;
+            if let Some(cmb) = cd.phase_gf_combination() {
+                tracker.gf_buffer.push(cd.t, cmb.value);
+            }
+            if let Some(cmb) = cd.mw_combination() {
+                let lambda_w = wide_lane_wavelength(cmb.reference, cmb.lhs);
+                tracker.mw_tracker.average(cmb.value / lambda_w);
+            }
+            for ph in cd.phase_range.iter() {
+                let doppler = cd
+                    .doppler
+                    .iter()
+                    .find(|dop| dop.carrier == ph.carrier)
+                    .map(|dop| dop.value);
+                tracker.phases.insert(ph.carrier, (ph.value, doppler));
+            }
+        }
+        slips
+    }
+    /*
+     * Receiver clock jump [m]: common mode of the Doppler residuals, that shifts
+     * the phase observations of every vehicle by the same range (~300 km per ms).
+     * Median of one residual per tracked vehicle, null when less than 3 vehicles
+     * contribute: one cycle slip could not be told from a clock jump.
+     */
+    fn clock_jump(&self, pool: &[Candidate]) -> f64 {
+        let mut residuals = pool
+            .iter()
+            .filter_map(|cd| {
+                let tracker = self.sv_trackers.get(&cd.sv)?;
+                let dt = cd.t - tracker.last_seen;
+                if dt > self.opts.gap_tolerance {
+                    return None;
+                }
+                cd.phase_range
+                    .iter()
+                    .find_map(|ph| Self::doppler_residual(cd, tracker, ph, dt.to_seconds()))
+            })
+            .collect::<Vec<_>>();
+        let n = residuals.len();
+        if n < 3 {
+            return 0.0;
+        }
+        residuals.sort_by(|a, b| a.total_cmp(b));
+        let median = if n % 2 == 1 {
+            residuals[n / 2]
+        } else {
+            (residuals[n / 2 - 1] + residuals[n / 2]) / 2.0
+        };
+        if median.abs() > self.opts.doppler_slip_threshold * Carrier::L1.wavelength() {
+            debug!("receiver clock jump: {:.3} m", median);
+        }
+        median
+    }
+    /*
+     * Phase variation [m] since previous observation, not explained
+     * by the Doppler shifts. None when Doppler shifts are missing.
+     */
+    fn doppler_residual(
+        cd: &Candidate,
+        tracker: &SVTracker,
+        ph: &PhaseRange,
+        dt: f64,
+    ) -> Option<f64> {
+        let (prev_phase, prev_doppler) = match tracker.phases.get(&ph.carrier) {
+            Some((phase, Some(doppler))) => (*phase, *doppler),
+            _ => return None,
+        };
+        let doppler = cd
+            .doppler
+            .iter()
+            .find(|dop| dop.carrier == ph.carrier)?
+            .value;
+        // positive Doppler shift: decreasing range
+        let predicted = -ph.carrier.wavelength() * (doppler + prev_doppler) / 2.0 * dt;
+        Some((ph.value - prev_phase) - predicted)
+    }
+    /*
+     * Runs all tests, returns the first failure.
+     * clock_jump [m] is removed from the Doppler residuals.
+     */
+    fn test(
+        cd: &Candidate,
+        tracker: &SVTracker,
+        opts: &AmbiguityOpts,
+        clock_jump: f64,
+    ) -> Option<SlipCause> {
+        let dt = cd.t - tracker.last_seen;
+        if dt > opts.gap_tolerance {
+            return Some(SlipCause::DataGap);
+        }
+
+        for ph in cd.phase_range.iter() {
+            let lli = ph.lli.map(|lli| lli & 0x01 > 0).unwrap_or(false);
+            let relocked = ph.lock_time.map(|lock| lock < dt).unwrap_or(false);
+            if lli || relocked {
+                return Some(SlipCause::LossOfLock);
+            }
+        }
+
+        if let Some(cmb) = cd.phase_gf_combination() {
+            if let Some((t0, fit)) = tracker.gf_buffer.polyfit(2) {
+                let t = (cd.t - t0).to_seconds();
+                let (a2, a1, a0) = (fit[2], fit[1], fit[0]);
+                let predicted = a2 * t.powi(2) + a1 * t + a0;
+                if (cmb.value - predicted).abs() > opts.slip_threshold {
+                    return Some(SlipCause::GeometryFree);
+                }
+            }
+        }
+
+        if let Some(cmb) = cd.mw_combination() {
+            if tracker.mw_tracker.n > 1 {
+                let lambda_w = wide_lane_wavelength(cmb.reference, cmb.lhs);
+                let mean = tracker.mw_tracker.mean();
+                if (cmb.value / lambda_w - mean).abs() > opts.mw_slip_threshold {
+                    return Some(SlipCause::MelbourneWubbena);
+                }
+            }
+        }
+
+        let dt = dt.to_seconds();
+        for ph in cd.phase_range.iter() {
+            if let Some(residual) = Self::doppler_residual(cd, tracker, ph, dt) {
+                let lambda = ph.carrier.wavelength();
+                if (residual - clock_jump).abs() / lambda > opts.doppler_slip_threshold {
+                    return Some(SlipCause::Doppler);
+                }
+            }
+        }
+        None
+    }
+}
+
+/*
+ * Wide lane wavelength [m]
+ */
+fn wide_lane_wavelength(reference: Carrier, lhs: Carrier) -> f64 {
+    SPEED_OF_LIGHT / (reference.frequency() - lhs.frequency())
+}
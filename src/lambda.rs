@@ -0,0 +1,305 @@
+//! Integer least-squares ambiguity resolution (LAMBDA method).
+//!
+//! References:
+//! - P.J.G. Teunissen, "The least-squares ambiguity decorrelation adjustment:
+//!   a method for fast GPS integer ambiguity estimation", J. Geodesy, 1995.
+//! - X.-W. Chang, X. Yang, T. Zhou, "MLAMBDA: a modified LAMBDA method for
+//!   integer least-squares estimation", J. Geodesy, 2005.
+use crate::stats::normal_q;
+use nalgebra::{DMatrix, DVector};
+
+/// Maximal number of search iterations
+const MAX_SEARCH_ITER: usize = 10_000;
+
+/// Integer least-squares solution
+#[derive(Clone, Debug)]
+pub(crate) struct IntegerSolution {
+    /// Best integer candidate
+    pub fixed: DVector<f64>,
+    /// Squared (Q weighted) norm of the best and second best candidates
+    pub norms: (f64, f64),
+    /// Bootstrapped success rate of the decorrelated ambiguities
+    pub success_rate: f64,
+}
+
+impl IntegerSolution {
+    /// Ratio test statistic: second best over best squared norm
+    pub fn ratio(&self) -> f64 {
+        if self.norms.0 > 0.0 {
+            self.norms.1 / self.norms.0
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+/// Rounds to nearest integer, half integers being rounded up
+fn round(x: f64) -> f64 {
+    (x + 0.5).floor()
+}
+
+fn sign(x: f64) -> f64 {
+    if x <= 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+/// Q = L' diag(D) L factorization, with L unit lower triangular
+fn ltdl(q: &DMatrix<f64>) -> Option<(DMatrix<f64>, DVector<f64>)> {
+    let n = q.nrows();
+    let mut a = q.clone();
+    let mut l = DMatrix::<f64>::zeros(n, n);
+    let mut d = DVector::<f64>::zeros(n);
+    for i in (0..n).rev() {
+        d[i] = a[(i, i)];
+        if d[i] <= 0.0 {
+            return None;
+        }
+        let s = d[i].sqrt();
+        for j in 0..=i {
+            l[(i, j)] = a[(i, j)] / s;
+        }
+        for j in 0..i {
+            for k in 0..=j {
+                a[(j, k)] -= l[(i, k)] * l[(i, j)];
+            }
+        }
+        let l_ii = l[(i, i)];
+        for j in 0..=i {
+            l[(i, j)] /= l_ii;
+        }
+    }
+    Some((l, d))
+}
+
+/// Integer Gauss transformation, applied to column j
+fn gauss(l: &mut DMatrix<f64>, z: &mut DMatrix<f64>, i: usize, j: usize) {
+    let n = l.nrows();
+    let mu = round(l[(i, j)]);
+    if mu != 0.0 {
+        for k in i..n {
+            l[(k, j)] -= mu * l[(k, i)];
+        }
+        for k in 0..n {
+            z[(k, j)] -= mu * z[(k, i)];
+        }
+    }
+}
+
+/// Permutation of conditional variances j and j+1
+fn permute(l: &mut DMatrix<f64>, d: &mut DVector<f64>, j: usize, delta: f64, z: &mut DMatrix<f64>) {
+    let n = l.nrows();
+    let eta = d[j] / delta;
+    let lambda = d[j + 1] * l[(j + 1, j)] / delta;
+    d[j] = eta * d[j + 1];
+    d[j + 1] = delta;
+    for k in 0..j {
+        let (a0, a1) = (l[(j, k)], l[(j + 1, k)]);
+        l[(j, k)] = -l[(j + 1, j)] * a0 + a1;
+        l[(j + 1, k)] = eta * a0 + lambda * a1;
+    }
+    l[(j + 1, j)] = lambda;
+    for k in j + 2..n {
+        l.swap((k, j), (k, j + 1));
+    }
+    for k in 0..n {
+        z.swap((k, j), (k, j + 1));
+    }
+}
+
+/// Decorrelation: z = Z' a, Qz = Z' Q Z = L' diag(D) L
+fn reduction(l: &mut DMatrix<f64>, d: &mut DVector<f64>) -> DMatrix<f64> {
+    let n = l.nrows();
+    let mut z = DMatrix::<f64>::identity(n, n);
+    if n < 2 {
+        return z;
+    }
+    let (mut j, mut k) = (n as isize - 2, n as isize - 2);
+    while j >= 0 {
+        let ju = j as usize;
+        if j <= k {
+            for i in ju + 1..n {
+                gauss(l, &mut z, i, ju);
+            }
+        }
+        let delta = d[ju] + l[(ju + 1, ju)].powi(2) * d[ju + 1];
+        // compared considering numerical errors
+        if delta + 1.0E-6 < d[ju + 1] {
+            permute(l, d, ju, delta, &mut z);
+            k = j;
+            j = n as isize - 2;
+        } else {
+            j -= 1;
+        }
+    }
+    z
+}
+
+/// Depth first search of the `m` best integer candidates (MLAMBDA).
+/// Returns candidates (columns) and their squared norms, in ascending order.
+fn search(
+    l: &DMatrix<f64>,
+    d: &DVector<f64>,
+    zs: &DVector<f64>,
+    m: usize,
+) -> Option<(DMatrix<f64>, Vec<f64>)> {
+    let n = l.nrows();
+    let mut s = DMatrix::<f64>::zeros(n, n);
+    let mut dist = vec![0.0_f64; n];
+    let mut zb = vec![0.0_f64; n];
+    let mut z = vec![0.0_f64; n];
+    let mut step = vec![0.0_f64; n];
+
+    let mut zn = DMatrix::<f64>::zeros(n, m);
+    let mut norms = Vec::<f64>::with_capacity(m);
+    let mut max_dist = f64::MAX;
+    let mut imax = 0;
+
+    let mut k = n - 1;
+    zb[k] = zs[k];
+    z[k] = round(zb[k]);
+    let mut y = zb[k] - z[k];
+    step[k] = sign(y);
+
+    let mut iter = 0;
+    while iter < MAX_SEARCH_ITER {
+        iter += 1;
+        let new_dist = dist[k] + y * y / d[k];
+        if new_dist < max_dist {
+            if k != 0 {
+                k -= 1;
+                dist[k] = new_dist;
+                for i in 0..=k {
+                    s[(k, i)] = s[(k + 1, i)] + (z[k + 1] - zb[k + 1]) * l[(k + 1, i)];
+                }
+                zb[k] = zs[k] + s[(k, k)];
+                z[k] = round(zb[k]);
+                y = zb[k] - z[k];
+                step[k] = sign(y);
+            } else {
+                if norms.len() < m {
+                    if norms.is_empty() || new_dist > norms[imax] {
+                        imax = norms.len();
+                    }
+                    zn.set_column(norms.len(), &DVector::from_column_slice(&z));
+                    norms.push(new_dist);
+                } else {
+                    if new_dist < norms[imax] {
+                        zn.set_column(imax, &DVector::from_column_slice(&z));
+                        norms[imax] = new_dist;
+                        imax = (0..m)
+                            .max_by(|a, b| norms[*a].total_cmp(&norms[*b]))
+                            .unwrap_or(0);
+                    }
+                    max_dist = norms[imax];
+                }
+                z[0] += step[0];
+                y = zb[0] - z[0];
+                step[0] = -step[0] - sign(step[0]);
+            }
+        } else if k == n - 1 {
+            break;
+        } else {
+            k += 1;
+            z[k] += step[k];
+            y = zb[k] - z[k];
+            step[k] = -step[k] - sign(step[k]);
+        }
+    }
+    if iter >= MAX_SEARCH_ITER || norms.len() < m {
+        return None;
+    }
+    // sort by norm
+    let mut order = (0..m).collect::<Vec<_>>();
+    order.sort_by(|a, b| norms[*a].total_cmp(&norms[*b]));
+    let sorted = DMatrix::<f64>::from_fn(n, m, |i, j| zn[(i, order[j])]);
+    let norms = order.iter().map(|j| norms[*j]).collect();
+    Some((sorted, norms))
+}
+
+/*
+ * Bootstrapped success rate, from the conditional variances
+ * of the decorrelated ambiguities
+ */
+fn bootstrapped_success_rate(d: &DVector<f64>) -> f64 {
+    d.iter()
+        .map(|d_i| 1.0 - 2.0 * normal_q(1.0 / (2.0 * d_i.sqrt())))
+        .product()
+}
+
+/*
+ * Resolves the integer least-squares problem of float ambiguities `a`
+ * [cycles] with covariance `q` [cycles²]: decorrelation, then search of
+ * the two best integer candidates. Returns None when `q` is not positive definite.
+ */
+pub(crate) fn resolve(a: &DVector<f64>, q: &DMatrix<f64>) -> Option<IntegerSolution> {
+    let n = a.len();
+    if n == 0 || q.nrows() != n || q.ncols() != n {
+        return None;
+    }
+    let (mut l, mut d) = ltdl(q)?;
+    let z = reduction(&mut l, &mut d);
+    let zs = z.transpose() * a;
+    let (zn, norms) = search(&l, &d, &zs, 2)?;
+    // back transformation: a = Z'^-1 z
+    let candidates = z.transpose().lu().solve(&zn)?;
+    let fixed = candidates.column(0).map(round);
+    Some(IntegerSolution {
+        fixed,
+        norms: (norms[0], norms[1]),
+        success_rate: bootstrapped_success_rate(&d),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ltdl, resolve};
+    use nalgebra::{DMatrix, DVector};
+    #[test]
+    fn factorization() {
+        let q = DMatrix::<f64>::from_row_slice(
+            3,
+            3,
+            &[
+                6.290, 5.978, 0.544, 5.978, 6.292, 2.340, 0.544, 2.340, 6.288,
+            ],
+        );
+        let (l, d) = ltdl(&q).unwrap();
+        let rebuilt = l.transpose() * DMatrix::from_diagonal(&d) * &l;
+        assert!((rebuilt - q).abs().max() < 1.0E-9);
+        for i in 0..3 {
+            assert_eq!(l[(i, i)], 1.0);
+        }
+    }
+    #[test]
+    fn lambda() {
+        // Teunissen's textbook example
+        let a = DVector::<f64>::from_column_slice(&[5.450, 3.100, 2.970]);
+        let q = DMatrix::<f64>::from_row_slice(
+            3,
+            3,
+            &[
+                6.290, 5.978, 0.544, 5.978, 6.292, 2.340, 0.544, 2.340, 6.288,
+            ],
+        );
+        let solution = resolve(&a, &q).unwrap();
+        assert_eq!(solution.fixed.as_slice(), &[5.0, 3.0, 4.0]);
+        assert!(solution.norms.0 <= solution.norms.1);
+        assert!(solution.ratio() >= 1.0);
+        assert!(solution.success_rate > 0.0 && solution.success_rate < 1.0);
+
+        // precise, well separated float ambiguities
+        let a = DVector::<f64>::from_column_slice(&[10.02, -3.01, 7.98, 0.03]);
+        let q = DMatrix::<f64>::identity(4, 4) * 1.0E-3;
+        let solution = resolve(&a, &q).unwrap();
+        assert_eq!(solution.fixed.as_slice(), &[10.0, -3.0, 8.0, 0.0]);
+        assert!(solution.ratio() > 3.0);
+        assert!(solution.success_rate > 0.999);
+
+        // not positive definite
+        let q = DMatrix::<f64>::zeros(4, 4);
+        assert!(resolve(&a, &q).is_none());
+    }
+}
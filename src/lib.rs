@@ -6,16 +6,22 @@ extern crate nyx_space as nyx;
 
 // private modules
 mod ambiguity;
+mod antex;
+mod attitude;
 mod bancroft;
 mod bias;
 mod candidate;
 mod carrier;
 mod cfg;
+mod cycle_slip;
+mod lambda;
 mod navigation;
 mod position;
+mod smoothing;
 mod solver;
+mod stats;
+mod tides;
 
-// mod tracker;
 // pub(crate) mod utils;
 
 #[cfg(test)]
@@ -23,12 +29,20 @@ mod tests;
 
 // prelude
 pub mod prelude {
-    pub use crate::ambiguity::Ambiguities;
+    pub use crate::ambiguity::{Ambiguities, Ambiguity};
+    pub use crate::antex::{Antenna, Antex, AntexError, FrequencyPattern};
     pub use crate::bias::{BdModel, IonosphereBias, KbModel, NgModel, TroposphereBias};
-    pub use crate::candidate::{Candidate, PhaseRange, PseudoRange};
-    pub use crate::carrier::Carrier;
-    pub use crate::cfg::{Config, Method};
-    pub use crate::navigation::{Filter, InvalidationCause, PVTSolution, PVTSolutionType};
+    pub use crate::candidate::{Candidate, Doppler, PhaseRange, PseudoRange};
+    pub use crate::carrier::{Carrier, ParsingError};
+    pub use crate::cfg::{
+        AmbiguityOpts, ChannelBias, Config, ElevationMappingFunction, FilterOpts, IntegrityOpts,
+        InterFrequencyBias, InterSystemBias, Method, NoiseModel, Observable, Profile, RaimOpts,
+        RangeErrorModel, SignalPriority, SystemTimeOffset, WeightMatrix,
+    };
+    pub use crate::cycle_slip::{CycleSlip, CycleSlipDetector, SlipCause};
+    pub use crate::navigation::{
+        Filter, FixedSolution, InvalidationCause, PVTSolution, PVTSolutionType,
+    };
     pub use crate::position::Position;
     pub use crate::solver::{Error, InterpolationResult, Solver};
     // re-export
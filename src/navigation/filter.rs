@@ -1,10 +1,16 @@
-use nalgebra::{base::dimension::U8, OMatrix, OVector, Vector3};
+use log::debug;
+use nalgebra::{DMatrix, DVector, Vector3};
 
 #[cfg(feature = "serde")]
 use serde::Deserialize;
 
-use super::{Input, Output};
-use crate::prelude::{Epoch, Error};
+use std::collections::HashMap;
+
+use super::{Input, Output, StateKind, StateLayout};
+use crate::{
+    cfg::{Config, FilterOpts, Profile, SolverOpts},
+    prelude::{Epoch, Error, SV},
+};
 
 /// Navigation Filter.
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
@@ -16,22 +22,95 @@ pub enum Filter {
     /// LSQ Filter. Heavy computation.
     LSQ,
     /// Kalman Filter. Heavy+ computations. Compared to LSQ, the Kalman filter
-    /// converges faster and has the ability to "improve" models
+    /// converges faster and has the ability to "improve" models.
+    /// The receiver dynamics are defined by the selected [Profile].
     Kalman,
 }
 
-#[derive(Debug, Clone, Default)]
+/// Initial variance of newly introduced states
+const INITIAL_VARIANCE: f64 = 1.0E6;
+
+#[derive(Debug, Clone)]
 struct LSQState {
-    pub p: OMatrix<f64, U8, U8>,
-    pub x: OVector<f64, U8>,
+    pub layout: StateLayout,
+    pub p: DMatrix<f64>,
+    pub x: DVector<f64>,
+}
+
+impl Default for LSQState {
+    fn default() -> Self {
+        Self {
+            layout: StateLayout::default(),
+            p: DMatrix::<f64>::zeros(0, 0),
+            x: DVector::<f64>::zeros(0),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Inter System Bias random walk noise [m²/s]
+const INTER_SYSTEM_BIAS_NOISE: f64 = 1.0E-4;
+
+#[derive(Debug, Clone)]
 struct KFState {
-    pub q: OMatrix<f64, U8, U8>,
-    pub p: OMatrix<f64, U8, U8>,
-    pub x: OVector<f64, U8>,
-    pub phi: OMatrix<f64, U8, U8>,
+    pub layout: StateLayout,
+    pub p: DMatrix<f64>,
+    pub x: DVector<f64>,
+}
+
+impl KFState {
+    /*
+     * State transition and process noise, for given layout, receiver [Profile],
+     * filter options and elapsed time [s] since previous state.
+     * Receivers held in static have a constant position,
+     * roaming receivers follow a white noise acceleration model.
+     */
+    fn dynamics(
+        layout: &StateLayout,
+        profile: Profile,
+        opts: &FilterOpts,
+        dt: f64,
+    ) -> (DMatrix<f64>, DMatrix<f64>) {
+        let nstates = layout.len();
+        let mut phi = DMatrix::<f64>::identity(nstates, nstates);
+        let mut q = DMatrix::<f64>::zeros(nstates, nstates);
+        if let Some(psd) = profile.acceleration_psd() {
+            for (pos, vel) in StateLayout::POSITION.iter().zip(StateLayout::VELOCITY) {
+                if let (Some(pos), Some(vel)) = (layout.index(*pos), layout.index(vel)) {
+                    phi[(pos, vel)] = dt;
+                    q[(pos, pos)] = psd * dt.powi(3) / 3.0;
+                    q[(pos, vel)] = psd * dt.powi(2) / 2.0;
+                    q[(vel, pos)] = q[(pos, vel)];
+                    q[(vel, vel)] = psd * dt;
+                }
+            }
+        }
+        if let (Some(offset), Some(drift)) = (
+            layout.index(StateKind::ClockOffset),
+            layout.index(StateKind::ClockDrift),
+        ) {
+            // two states clock model
+            phi[(offset, drift)] = dt;
+            q[(offset, offset)] =
+                opts.clock_offset_psd * dt + opts.clock_drift_psd * dt.powi(3) / 3.0;
+            q[(offset, drift)] = opts.clock_drift_psd * dt.powi(2) / 2.0;
+            q[(drift, offset)] = q[(offset, drift)];
+            q[(drift, drift)] = opts.clock_drift_psd * dt;
+        }
+        for (i, kind) in layout.iter().enumerate() {
+            match kind {
+                StateKind::InterSystemBias(_) => {
+                    // slowly varying system time offsets
+                    q[(i, i)] = INTER_SYSTEM_BIAS_NOISE * dt;
+                },
+                StateKind::TropoZwd => {
+                    // slowly varying wet delay
+                    q[(i, i)] = opts.tropo_psd * dt;
+                },
+                _ => {},
+            }
+        }
+        (phi, q)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -46,164 +125,389 @@ impl Default for FilterState {
     }
 }
 
+/*
+ * Projects previous state (x, p) onto a new layout.
+ * States that are no longer estimated are dropped,
+ * new states are introduced with a null estimate and large variance.
+ */
+fn remap(
+    prev: &StateLayout,
+    x: &DVector<f64>,
+    p: &DMatrix<f64>,
+    layout: &StateLayout,
+) -> (DVector<f64>, DMatrix<f64>) {
+    // clock offset and inter system biases are relative to the reference
+    // system time: they are new states when the reference changed
+    let reset = prev.reference() != layout.reference();
+    let prev_index = |kind: &StateKind| match kind {
+        StateKind::ClockOffset | StateKind::InterSystemBias(_) if reset => None,
+        _ => prev.index(*kind),
+    };
+    let nstates = layout.len();
+    let mut new_x = DVector::<f64>::zeros(nstates);
+    let mut new_p = DMatrix::<f64>::zeros(nstates, nstates);
+    for (i, kind_i) in layout.iter().enumerate() {
+        match prev_index(kind_i) {
+            Some(prev_i) => {
+                new_x[i] = x[prev_i];
+                for (j, kind_j) in layout.iter().enumerate() {
+                    if let Some(prev_j) = prev_index(kind_j) {
+                        new_p[(i, j)] = p[(prev_i, prev_j)];
+                    }
+                }
+            },
+            None => {
+                new_p[(i, i)] = INITIAL_VARIANCE;
+            },
+        }
+    }
+    (new_x, new_p)
+}
+
 impl FilterState {
     fn lsq(state: LSQState) -> Self {
         Self::Lsq(state)
     }
-    //fn as_lsq(&self) -> Option<&LSQState> {
-    //    match self {
-    //        Self::LSQ(state) => Some(state),
-    //        _ => None,
-    //    }
-    //}
-    pub fn ambiguities(&self) -> Vec<f64> {
-        let x = match self {
-            Self::Lsq(state) => state.x,
-            Self::Kf(state) => state.x,
-        };
-        let mut r = Vec::<f64>::new();
-        for i in 4..x.len() {
-            r.push(x[i]);
+    /// Returns [StateLayout] of this state
+    pub fn layout(&self) -> &StateLayout {
+        match self {
+            Self::Lsq(state) => &state.layout,
+            Self::Kf(state) => &state.layout,
         }
-        r
+    }
+    /// Returns estimated float ambiguities [m], per SV: corrections
+    /// to the a priori ambiguity of each phase tracking arc
+    pub fn ambiguities(&self) -> HashMap<SV, f64> {
+        let x = self.estimate();
+        self.layout()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, kind)| match kind {
+                StateKind::Ambiguity(sv, _) => Some((*sv, x[i])),
+                _ => None,
+            })
+            .collect()
     }
     fn kf(state: KFState) -> Self {
         Self::Kf(state)
     }
-    //fn as_kf(&self) -> Option<&KFState> {
-    //    match self {
-    //        Self::KF(state) => Some(state),
-    //        _ => None,
-    //    }
-    //}
-    pub(crate) fn estimate(&self) -> OVector<f64, U8> {
+    pub(crate) fn estimate(&self) -> DVector<f64> {
         match self {
-            Self::Lsq(state) => state.x,
-            Self::Kf(state) => state.x,
+            Self::Lsq(state) => state.x.clone(),
+            Self::Kf(state) => state.x.clone(),
+        }
+    }
+    /// Returns estimated value of given state, if it is part of the layout
+    pub(crate) fn value(&self, kind: StateKind) -> Option<f64> {
+        let index = self.layout().index(kind)?;
+        Some(self.estimate()[index])
+    }
+    /// Returns covariance matrix of given states, if they are all part of the layout
+    pub(crate) fn covariance(&self, kinds: &[StateKind]) -> Option<DMatrix<f64>> {
+        let indices = kinds
+            .iter()
+            .map(|kind| self.layout().index(*kind))
+            .collect::<Option<Vec<_>>>()?;
+        let p = match self {
+            Self::Lsq(state) => &state.p,
+            Self::Kf(state) => &state.p,
+        };
+        Some(DMatrix::<f64>::from_fn(
+            indices.len(),
+            indices.len(),
+            |i, j| p[(indices[i], indices[j])],
+        ))
+    }
+    /// Returns the state estimate, conditioned on given states being
+    /// shifted by `residuals` (float minus constrained value):
+    /// x - P_xa P_aa^-1 residuals.
+    pub(crate) fn conditioned(
+        &self,
+        kinds: &[StateKind],
+        residuals: &DVector<f64>,
+    ) -> Option<DVector<f64>> {
+        let indices = kinds
+            .iter()
+            .map(|kind| self.layout().index(*kind))
+            .collect::<Option<Vec<_>>>()?;
+        let p = match self {
+            Self::Lsq(state) => &state.p,
+            Self::Kf(state) => &state.p,
+        };
+        let p_xa = DMatrix::<f64>::from_fn(p.nrows(), indices.len(), |i, j| p[(i, indices[j])]);
+        let p_aa = DMatrix::<f64>::from_fn(indices.len(), indices.len(), |i, j| {
+            p[(indices[i], indices[j])]
+        });
+        let correction = p_aa.cholesky()?.solve(residuals);
+        Some(self.estimate() - p_xa * correction)
+    }
+    /// Returns variance of given state, if it is part of the layout
+    pub(crate) fn variance(&self, kind: StateKind) -> Option<f64> {
+        let index = self.layout().index(kind)?;
+        let p = match self {
+            Self::Lsq(state) => &state.p,
+            Self::Kf(state) => &state.p,
+        };
+        Some(p[(index, index)])
+    }
+    /*
+     * Projects self onto new layout
+     */
+    pub(crate) fn remap(&self, layout: &StateLayout) -> Self {
+        match self {
+            Self::Lsq(state) => {
+                let (x, p) = remap(&state.layout, &state.x, &state.p, layout);
+                Self::lsq(LSQState {
+                    layout: layout.clone(),
+                    x,
+                    p,
+                })
+            },
+            Self::Kf(state) => {
+                let (x, p) = remap(&state.layout, &state.x, &state.p, layout);
+                Self::kf(KFState {
+                    layout: layout.clone(),
+                    x,
+                    p,
+                })
+            },
         }
     }
 }
 
-impl Filter {
-    fn lsq_resolve(input: &Input, p_state: Option<FilterState>) -> Result<Output, Error> {
-        match p_state {
-            Some(FilterState::Lsq(p_state)) => {
-                let p_1 = p_state.p.try_inverse().ok_or(Error::MatrixInversionError)?;
+/*
+ * Geometry cofactor matrix (G'G)^-1, restricted to the states
+ * that are observed. Unobserved states have null terms.
+ */
+fn geometry(g: &DMatrix<f64>) -> Result<DMatrix<f64>, Error> {
+    let nstates = g.ncols();
+    let observed = (0..nstates)
+        .filter(|j| g.column(*j).iter().any(|g_ij| *g_ij != 0.0))
+        .collect::<Vec<_>>();
+    let g_obs = g.select_columns(observed.iter());
+    let q_obs = (g_obs.transpose() * &g_obs)
+        .try_inverse()
+        .ok_or(Error::MatrixInversionError)?;
+    let mut q = DMatrix::<f64>::zeros(nstates, nstates);
+    for (i, obs_i) in observed.iter().enumerate() {
+        for (j, obs_j) in observed.iter().enumerate() {
+            q[(*obs_i, *obs_j)] = q_obs[(i, j)];
+        }
+    }
+    Ok(q)
+}
 
-                let g_prime = input.g.clone().transpose();
-                let q = (g_prime * input.g)
-                    .try_inverse()
-                    .ok_or(Error::MatrixInversionError)?;
+/*
+ * Dilution of precision (gdop, pdop, tdop) from Q matrix
+ */
+fn dops(q: &DMatrix<f64>, layout: &StateLayout) -> (f64, f64, f64) {
+    let mut pos_var = 0.0_f64;
+    for kind in StateLayout::POSITION {
+        if let Some(i) = layout.index(kind) {
+            pos_var += q[(i, i)];
+        }
+    }
+    let time_var = match layout.index(StateKind::ClockOffset) {
+        Some(i) => q[(i, i)],
+        None => 0.0_f64,
+    };
+    ((pos_var + time_var).sqrt(), pos_var.sqrt(), time_var.sqrt())
+}
 
-                let p = g_prime * input.w * input.g;
-                let p = (p_1 + p).try_inverse().ok_or(Error::MatrixInversionError)?;
+/*
+ * Position innovation [m]: norm of the position correction
+ */
+fn innovation(dx: &DVector<f64>, layout: &StateLayout) -> f64 {
+    let mut norm = 0.0_f64;
+    for kind in StateLayout::POSITION {
+        if let Some(i) = layout.index(kind) {
+            norm += dx[i].powi(2);
+        }
+    }
+    norm.sqrt()
+}
 
-                let x = p * (p_1 * p_state.x + (g_prime * input.w * input.y));
+impl Filter {
+    /*
+     * (Weighted) Least Squares, with Gauss-Newton iterations:
+     * observations are linearized again around the latest position estimate,
+     * until the position correction converges.
+     */
+    fn lsq_resolve(
+        input: &Input,
+        p_state: Option<FilterState>,
+        opts: &SolverOpts,
+    ) -> Result<Output, Error> {
+        let layout = input.layout.clone();
+        let nstates = layout.len();
 
-                Ok(Output {
-                    gdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)] + q[(3, 3)]).sqrt(),
-                    pdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)]).sqrt(),
-                    tdop: q[(4, 3)].sqrt(),
-                    q,
-                    state: FilterState::lsq(LSQState { p, x }),
-                })
+        // prior information
+        let prior = match p_state {
+            Some(FilterState::Lsq(p_state)) => {
+                let p_1 = p_state.p.try_inverse().ok_or(Error::MatrixInversionError)?;
+                Some((p_1, p_state.x))
             },
-            _ => {
-                let g_prime = input.g.clone().transpose();
+            _ => None,
+        };
 
-                let q = (g_prime * input.g)
-                    .try_inverse()
-                    .ok_or(Error::MatrixInversionError)?;
+        // total position correction, applied to the linearization point
+        let mut offset = DVector::<f64>::zeros(nstates);
+        let mut lin = input.clone();
+        let mut iterations = 0;
 
-                let p = (g_prime * input.w * input.g)
-                    .try_inverse()
-                    .ok_or(Error::MatrixInversionError)?;
+        let (p, x, correction) = loop {
+            iterations += 1;
+            let g_prime = lin.g.transpose();
+            let n = &g_prime * &lin.w * &lin.g;
+            let b = &g_prime * &lin.w * &lin.y;
 
-                let x = p * (g_prime * input.w * input.y);
-                if x[3].is_nan() {
-                    return Err(Error::TimeIsNan);
+            let (p, dx) = match &prior {
+                Some((p_1, x_p)) => {
+                    let p = (p_1 + n).try_inverse().ok_or(Error::MatrixInversionError)?;
+                    let dx = &p * (p_1 * (x_p - &offset) + b);
+                    (p, dx)
+                },
+                None => {
+                    let p = n.try_inverse().ok_or(Error::MatrixInversionError)?;
+                    let dx = &p * b;
+                    (p, dx)
+                },
+            };
+
+            let correction = innovation(&dx, &layout);
+            if iterations >= opts.max_iterations
+                || correction < opts.convergence_threshold
+                || !layout.has_position()
+            {
+                break (p, &offset + dx, correction);
+            }
+
+            let mut dpos = Vector3::<f64>::zeros();
+            for (i, kind) in StateLayout::POSITION.iter().enumerate() {
+                if let Some(index) = layout.index(*kind) {
+                    dpos[i] = dx[index];
+                    offset[index] += dx[index];
                 }
+            }
+            lin = lin.relinearize(&dpos);
+        };
 
-                Ok(Output {
-                    gdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)] + q[(3, 3)]).sqrt(),
-                    pdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)]).sqrt(),
-                    tdop: q[(4, 3)].sqrt(),
-                    q,
-                    state: FilterState::lsq(LSQState { p, x }),
-                })
-            },
+        debug!(
+            "lsq: {} iteration(s), last correction {:.3E}m",
+            iterations, correction
+        );
+
+        if let Some(index) = layout.index(StateKind::ClockOffset) {
+            if x[index].is_nan() {
+                return Err(Error::TimeIsNan);
+            }
         }
+
+        let q = geometry(&lin.g)?;
+        let (gdop, pdop, tdop) = dops(&q, &layout);
+
+        Ok(Output {
+            gdop,
+            pdop,
+            tdop,
+            q,
+            iterations,
+            correction,
+            innovation: prior
+                .as_ref()
+                .map(|(_, x_p)| innovation(&(&x - x_p), &layout)),
+            state: FilterState::lsq(LSQState { layout, p, x }),
+        })
     }
-    fn kf_resolve(input: &Input, p_state: Option<FilterState>) -> Result<Output, Error> {
+    fn kf_resolve(
+        input: &Input,
+        p_state: Option<FilterState>,
+        profile: Profile,
+        opts: &FilterOpts,
+        dt: f64,
+    ) -> Result<Output, Error> {
+        let layout = input.layout.clone();
+        let g_prime = input.g.transpose();
+        let q_n = geometry(&input.g)?;
+        let (gdop, pdop, tdop) = dops(&q_n, &layout);
+
         match p_state {
             Some(FilterState::Kf(p_state)) => {
-                let x_bn = p_state.phi * p_state.x;
-                let p_bn = p_state.phi * p_state.p * p_state.phi.transpose() + p_state.q;
+                let (phi, q) = KFState::dynamics(&layout, profile, opts, dt);
+                let x_bn = &phi * &p_state.x;
+                let p_bn = &phi * &p_state.p * phi.transpose() + q;
 
                 let p_bn_inv = p_bn.try_inverse().ok_or(Error::MatrixInversionError)?;
-                let p_n = (input.g.transpose() * input.w * input.g + p_bn_inv)
+                let p_n = (&g_prime * &input.w * &input.g + &p_bn_inv)
                     .try_inverse()
                     .ok_or(Error::MatrixInversionError)?;
 
-                let w_g = input.g.transpose() * input.w * input.y;
-                let w_gy_pbn = w_g + (p_bn_inv * x_bn);
-                let x_n = p_n * w_gy_pbn;
-
-                let q_n = input.g.transpose() * input.g;
-                let phi_diag = OVector::<f64, U8>::from([1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
-                let q_diag = OVector::<f64, U8>::from([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+                let w_g = &g_prime * &input.w * &input.y;
+                let w_gy_pbn = w_g + (&p_bn_inv * &x_bn);
+                let x_n = &p_n * w_gy_pbn;
 
                 Ok(Output {
-                    gdop: (q_n[(0, 0)] + q_n[(1, 1)] + q_n[(2, 2)] + q_n[(3, 3)]).sqrt(),
-                    pdop: (q_n[(0, 0)] + q_n[(1, 1)] + q_n[(2, 2)]).sqrt(),
-                    tdop: q_n[(4, 3)].sqrt(),
+                    gdop,
+                    pdop,
+                    tdop,
                     q: q_n,
+                    iterations: 1,
+                    correction: innovation(&(&x_n - &x_bn), &layout),
+                    innovation: Some(innovation(&(&x_n - &x_bn), &layout)),
                     state: FilterState::kf(KFState {
+                        layout,
                         p: p_n,
                         x: x_n,
-                        q: OMatrix::<f64, U8, U8>::from_diagonal(&q_diag),
-                        phi: OMatrix::<f64, U8, U8>::from_diagonal(&phi_diag),
                     }),
                 })
             },
             _ => {
-                let g_prime = input.g.clone().transpose();
-                let q = (g_prime * input.g)
-                    .try_inverse()
-                    .ok_or(Error::MatrixInversionError)?;
-
-                let p = (g_prime * input.w * input.g)
+                // states that are not observed yet (like the clock drift)
+                // are initialized with a null estimate and large variance
+                let nstates = layout.len();
+                let p_0_inv = DMatrix::<f64>::identity(nstates, nstates) / INITIAL_VARIANCE;
+                let p = (&g_prime * &input.w * &input.g + p_0_inv)
                     .try_inverse()
                     .ok_or(Error::MatrixInversionError)?;
 
-                let x = p * (g_prime * input.w * input.y);
-                if x[3].is_nan() {
-                    return Err(Error::TimeIsNan);
+                let x = &p * (&g_prime * &input.w * &input.y);
+                if let Some(index) = layout.index(StateKind::ClockOffset) {
+                    if x[index].is_nan() {
+                        return Err(Error::TimeIsNan);
+                    }
                 }
 
-                let phi_diag = OVector::<f64, U8>::from([1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
-                let q_diag = OVector::<f64, U8>::from([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
-
                 Ok(Output {
-                    gdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)] + q[(3, 3)]).sqrt(),
-                    pdop: (q[(0, 0)] + q[(1, 1)] + q[(2, 2)]).sqrt(),
-                    tdop: q[(4, 3)].sqrt(),
-                    q,
-                    state: FilterState::kf(KFState {
-                        p,
-                        x,
-                        q: OMatrix::<f64, U8, U8>::from_diagonal(&q_diag),
-                        phi: OMatrix::<f64, U8, U8>::from_diagonal(&phi_diag),
-                    }),
+                    gdop,
+                    pdop,
+                    tdop,
+                    q: q_n,
+                    iterations: 1,
+                    correction: innovation(&x, &layout),
+                    innovation: None,
+                    state: FilterState::kf(KFState { layout, p, x }),
                 })
             },
         }
     }
-    pub fn resolve(&self, input: &Input, p_state: Option<FilterState>) -> Result<Output, Error> {
+    /// Resolves the navigation problem.
+    /// - cfg: [Config] defines the receiver dynamics and iterative behavior
+    /// - dt: elapsed time [s] since the previous state was resolved.
+    pub fn resolve(
+        &self,
+        input: &Input,
+        p_state: Option<FilterState>,
+        cfg: &Config,
+        dt: f64,
+    ) -> Result<Output, Error> {
         match self {
-            Filter::None => Self::lsq_resolve(input, None),
-            Filter::LSQ => Self::lsq_resolve(input, p_state),
-            Filter::Kalman => Self::kf_resolve(input, p_state),
+            Filter::None => Self::lsq_resolve(input, None, &cfg.solver),
+            Filter::LSQ => Self::lsq_resolve(input, p_state, &cfg.solver),
+            Filter::Kalman => {
+                let default = FilterOpts::default();
+                let opts = cfg.solver.filter_opts.as_ref().unwrap_or(&default);
+                Self::kf_resolve(input, p_state, cfg.profile, opts, dt)
+            },
         }
     }
 }
@@ -246,3 +550,343 @@ impl std::fmt::Display for State3D {
 //         self.t = t;
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::{Filter, FilterState, KFState, INITIAL_VARIANCE};
+    use crate::{
+        ambiguity::PhaseArc,
+        cfg::FilterOpts,
+        navigation::{Input, StateKind, StateLayout},
+        prelude::{
+            Candidate, Config, Constellation, Duration, Epoch, Method, PVTSolutionType, Profile,
+            TimeScale, SV,
+        },
+        tests::geometry::{self, fixed_input},
+    };
+    use nalgebra::{DMatrix, DVector, Vector3};
+    #[test]
+    fn kf_process_noise() {
+        let mut cfg = Config::static_preset(Method::PPP);
+        cfg.solver.filter = Filter::Kalman;
+        let layout = StateLayout::new(&cfg, &[]);
+        let opts = FilterOpts {
+            clock_offset_psd: 2.0,
+            clock_drift_psd: 0.0,
+            tropo_psd: 1.0E-6,
+            ..Default::default()
+        };
+        let dt = 30.0;
+        let (phi, q) = KFState::dynamics(&layout, cfg.profile, &opts, dt);
+        let offset = layout.index(StateKind::ClockOffset).unwrap();
+        let drift = layout.index(StateKind::ClockDrift).unwrap();
+        let zwd = layout.index(StateKind::TropoZwd).unwrap();
+        assert_eq!(phi[(offset, drift)], dt);
+        assert_eq!(q[(offset, offset)], 2.0 * dt);
+        assert_eq!(q[(drift, drift)], 0.0);
+        assert_eq!(q[(zwd, zwd)], 1.0E-6 * dt);
+        // static receiver: constant position
+        for kind in StateLayout::POSITION {
+            let i = layout.index(kind).unwrap();
+            assert_eq!(q[(i, i)], 0.0);
+        }
+    }
+    #[test]
+    fn kf_clock_drift() {
+        let mut cfg = Config::static_preset(Method::SPP);
+        cfg.sol_type = PVTSolutionType::TimeOnly;
+        cfg.solver.filter = Filter::Kalman;
+        let layout = StateLayout::new(&cfg, &[]);
+        assert_eq!(layout.len(), 2);
+
+        // clock offset drifting at 2 m/s, 4 vehicles observing it
+        let (drift, dt) = (2.0_f64, 30.0_f64);
+        let mut state = Option::<FilterState>::None;
+        for epoch in 0..20 {
+            let offset = 1.0E3 + drift * dt * epoch as f64;
+            let input = fixed_input(
+                &layout,
+                DMatrix::<f64>::from_fn(4, 2, |_, j| if j == 0 { 1.0 } else { 0.0 }),
+                DVector::<f64>::from_element(4, offset),
+            );
+            let output = cfg.solver.filter.resolve(&input, state, &cfg, dt).unwrap();
+            state = Some(output.state);
+        }
+        let state = state.unwrap();
+        let estimate = state.value(StateKind::ClockDrift).unwrap();
+        assert!((estimate - drift).abs() < 1.0E-2, "drift: {}", estimate);
+        assert!(state.variance(StateKind::ClockDrift).unwrap() < 1.0);
+    }
+    #[test]
+    fn kf_position_velocity() {
+        let mut cfg = Config::static_preset(Method::SPP);
+        cfg.solver.filter = Filter::Kalman;
+        cfg.profile = Profile::Pedestrian;
+        let layout = StateLayout::new(&cfg, &[]);
+        assert!(layout.has_velocity());
+
+        // line of sight vectors
+        let los = [
+            (0.0, 0.0, 1.0),
+            (0.8, 0.0, 0.6),
+            (-0.8, 0.0, 0.6),
+            (0.0, 0.8, 0.6),
+            (0.0, -0.8, 0.6),
+            (0.5, 0.5, 0.707),
+        ];
+        let (velocity, dt) = ([1.0_f64, -0.5_f64, 0.2_f64], 1.0_f64);
+        let mut g = DMatrix::<f64>::zeros(los.len(), layout.len());
+        for (i, (x, y, z)) in los.iter().enumerate() {
+            for (kind, dx) in StateLayout::POSITION.iter().zip([x, y, z]) {
+                g[(i, layout.index(*kind).unwrap())] = *dx;
+            }
+            g[(i, layout.index(StateKind::ClockOffset).unwrap())] = 1.0;
+        }
+
+        let mut state = Option::<FilterState>::None;
+        let mut correction = 0.0;
+        for epoch in 0..60 {
+            let position = velocity.map(|v| v * dt * epoch as f64);
+            let y = DVector::<f64>::from_fn(los.len(), |i, _| {
+                los[i].0 * position[0] + los[i].1 * position[1] + los[i].2 * position[2] + 10.0
+            });
+            let input = fixed_input(&layout, g.clone(), y);
+            let output = cfg.solver.filter.resolve(&input, state, &cfg, dt).unwrap();
+            correction = output.correction;
+            state = Some(output.state);
+        }
+        // steady state: the measurement update barely corrects the prediction
+        assert!(correction < 0.1, "correction: {}", correction);
+        let state = state.unwrap();
+        for (kind, expected) in StateLayout::VELOCITY.iter().zip(velocity) {
+            let estimate = state.value(*kind).unwrap();
+            assert!(
+                (estimate - expected).abs() < 1.0E-2,
+                "{}: {} but {} is expected",
+                kind,
+                estimate,
+                expected
+            );
+        }
+        assert!(state.covariance(&StateLayout::VELOCITY).is_some());
+    }
+    #[test]
+    fn kf_float_ambiguities() {
+        let mut cfg = Config::static_preset(Method::PPP);
+        cfg.solver.filter = Filter::Kalman;
+        cfg.modeling.tropo_delay = false;
+
+        // line of sight vectors and IF ambiguities [m]
+        let los = [
+            (0.0, 0.0, 1.0),
+            (0.8, 0.0, 0.6),
+            (-0.8, 0.0, 0.6),
+            (0.0, 0.8, 0.6),
+            (0.0, -0.8, 0.6),
+            (0.5, 0.5, 0.707),
+        ];
+        let mut ambiguities = [12.3, -4.7, 8.1, 0.4, -15.9, 3.3];
+        let (position, clock) = ([0.3_f64, -0.2_f64, 0.5_f64], 7.0_f64);
+        let n = los.len();
+
+        let mut state = Option::<FilterState>::None;
+        for epoch in 0..200 {
+            let arc = if epoch < 100 { 0 } else { 1 };
+            if epoch == 100 {
+                // cycle slip on first SV
+                ambiguities[0] += 0.19;
+            }
+            let pool = (0..n)
+                .map(|i| {
+                    let mut cd = Candidate::new(
+                        SV::new(Constellation::GPS, i as u8 + 1),
+                        Epoch::default(),
+                        Duration::default(),
+                        None,
+                        vec![],
+                        vec![],
+                    );
+                    cd.phase_arc = Some(PhaseArc {
+                        id: if i == 0 { arc } else { 0 },
+                        apriori: 0.0,
+                    });
+                    cd
+                })
+                .collect::<Vec<_>>();
+            let layout = StateLayout::new(&cfg, &pool);
+            assert_eq!(layout.len(), 5 + n);
+
+            let mut g = DMatrix::<f64>::zeros(2 * n, layout.len());
+            let mut y = DVector::<f64>::zeros(2 * n);
+            let mut w = DMatrix::<f64>::identity(2 * n, 2 * n);
+            for (i, (x, y_los, z)) in los.iter().enumerate() {
+                let range = x * position[0] + y_los * position[1] + z * position[2] + clock;
+                // zero mean code noise
+                let noise = 0.5 * (1.7 * epoch as f64 + i as f64).sin();
+                for row in [i, n + i] {
+                    for (kind, dx) in StateLayout::POSITION.iter().zip([x, y_los, z]) {
+                        g[(row, layout.index(*kind).unwrap())] = *dx;
+                    }
+                    g[(row, layout.index(StateKind::ClockOffset).unwrap())] = 1.0;
+                }
+                g[(n + i, layout.ambiguity(pool[i].sv).unwrap())] = 1.0;
+                y[i] = range + noise;
+                y[n + i] = range + ambiguities[i];
+                w[(n + i, n + i)] = 1.0E4;
+            }
+            let input = Input {
+                w,
+                ..fixed_input(&layout, g, y)
+            };
+            let p_state = state.map(|state| state.remap(&input.layout));
+            let output = cfg
+                .solver
+                .filter
+                .resolve(&input, p_state, &cfg, 1.0)
+                .unwrap();
+            state = Some(output.state);
+
+            if epoch == 0 {
+                // converging solutions from the first epoch
+                let state = state.as_ref().unwrap();
+                assert!(state.value(StateKind::PositionX).unwrap().abs() < 5.0);
+            }
+        }
+        let state = state.unwrap();
+        for (kind, expected) in StateLayout::POSITION.iter().zip(position) {
+            let estimate = state.value(*kind).unwrap();
+            assert!(
+                (estimate - expected).abs() < 5.0E-2,
+                "{}: {} but {} is expected",
+                kind,
+                estimate,
+                expected
+            );
+        }
+        let estimates = state.ambiguities();
+        for (i, expected) in ambiguities.iter().enumerate() {
+            let estimate = estimates[&SV::new(Constellation::GPS, i as u8 + 1)];
+            assert!(
+                (estimate - expected).abs() < 5.0E-2,
+                "amb #{}: {} but {} is expected",
+                i,
+                estimate,
+                expected
+            );
+        }
+        // previous arc is no longer estimated
+        let sv = SV::new(Constellation::GPS, 1);
+        assert!(state.value(StateKind::Ambiguity(sv, 0)).is_none());
+        assert!(state.value(StateKind::Ambiguity(sv, 1)).is_some());
+    }
+    #[test]
+    fn lsq_iterations() {
+        let mut cfg = Config::static_preset(Method::SPP);
+        let layout = StateLayout::new(&cfg, &[]);
+
+        let rx = geometry::rx();
+        let clock = 15.0_f64;
+        // poor apriori
+        let apriori = rx + Vector3::new(5.0E3, -3.0E3, 2.0E3);
+        let input = geometry::range_input(&layout, &geometry::vehicles(5), |_| clock, &apriori);
+
+        let output = cfg.solver.filter.resolve(&input, None, &cfg, 0.0).unwrap();
+        assert!(output.iterations > 1);
+        assert!(output.correction < cfg.solver.convergence_threshold);
+        for (i, kind) in StateLayout::POSITION.iter().enumerate() {
+            let estimate = output.state.value(*kind).unwrap();
+            assert!((apriori[i] + estimate - rx[i]).abs() < 1.0E-3);
+        }
+        let estimate = output.state.value(StateKind::ClockOffset).unwrap();
+        assert!((estimate - clock).abs() < 1.0E-3);
+
+        // single linearization: the solution carries linearization error
+        cfg.solver.max_iterations = 1;
+        let output = cfg.solver.filter.resolve(&input, None, &cfg, 0.0).unwrap();
+        assert_eq!(output.iterations, 1);
+        assert!(output.correction > 1.0);
+    }
+    #[test]
+    fn lsq_exclusion() {
+        let mut cfg = Config::static_preset(Method::SPP);
+        let (clock, isb) = (15.0_f64, 3.0_f64);
+        let mut sv = geometry::vehicles(6);
+        sv[5].0 = SV::new(Constellation::Galileo, 1);
+        let pool = sv
+            .iter()
+            .map(|(sv, _)| {
+                Candidate::new(
+                    *sv,
+                    Epoch::default(),
+                    Duration::default(),
+                    None,
+                    vec![],
+                    vec![],
+                )
+            })
+            .collect::<Vec<_>>();
+        let build = |layout: &StateLayout| {
+            let offset = |sv: SV| {
+                if sv.constellation == Constellation::Galileo {
+                    clock + isb
+                } else {
+                    clock
+                }
+            };
+            geometry::range_input(layout, &sv, offset, &geometry::rx())
+        };
+
+        let layout = StateLayout::new(&cfg, &pool);
+        let input = build(&layout);
+        let output = cfg.solver.filter.resolve(&input, None, &cfg, 0.0).unwrap();
+        let estimate = output
+            .state
+            .value(StateKind::InterSystemBias(Constellation::Galileo));
+        assert!((estimate.unwrap() - isb).abs() < 1.0E-3);
+
+        // excluding the only Galileo vehicle: its bias is no longer observed
+        let input = input.without(sv[5].0);
+        assert_eq!(input.nb_sv(), 5);
+        assert_eq!(input.layout.len(), layout.len() - 1);
+        assert_eq!(input.g.ncols(), layout.len() - 1);
+        assert!(input
+            .layout
+            .inter_system_bias(Constellation::Galileo)
+            .is_none());
+
+        let output = cfg.solver.filter.resolve(&input, None, &cfg, 0.0).unwrap();
+        for kind in StateLayout::POSITION {
+            assert!(output.state.value(kind).unwrap().abs() < 1.0E-3);
+        }
+        let estimate = output.state.value(StateKind::ClockOffset).unwrap();
+        assert!((estimate - clock).abs() < 1.0E-3);
+
+        // galileo is now the reference: excluding its only vehicle makes GPST
+        // the reference, the GPS bias is absorbed by the clock offset
+        cfg.timescale = TimeScale::GST;
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.reference(), Constellation::Galileo);
+        let full = build(&layout);
+        let output = cfg.solver.filter.resolve(&full, None, &cfg, 0.0).unwrap();
+        let estimate = output.state.value(StateKind::ClockOffset).unwrap();
+        assert!((estimate - clock - isb).abs() < 1.0E-3);
+
+        let input = full.without(sv[5].0);
+        assert_eq!(input.layout.reference(), Constellation::GPS);
+        assert_eq!(input.layout.len(), layout.len() - 1);
+        assert_eq!(input.g.ncols(), layout.len() - 1);
+        assert!(input.layout.inter_system_bias(Constellation::GPS).is_none());
+
+        // previous clock offset refers to another system time: it is reset
+        let remapped = output.state.remap(&input.layout);
+        assert_eq!(
+            remapped.variance(StateKind::ClockOffset),
+            Some(INITIAL_VARIANCE)
+        );
+        assert!(remapped.variance(StateKind::PositionX).unwrap() < INITIAL_VARIANCE);
+
+        let output = cfg.solver.filter.resolve(&input, None, &cfg, 0.0).unwrap();
+        let estimate = output.state.value(StateKind::ClockOffset).unwrap();
+        assert!((estimate - clock).abs() < 1.0E-3);
+    }
+}
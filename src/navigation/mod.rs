@@ -1,25 +1,32 @@
 pub mod solutions;
-pub use solutions::{InstrumentBias, InvalidationCause, PVTSolution, PVTSolutionType};
+pub use solutions::{
+    FixedSolution, InstrumentBias, InvalidationCause, PVTSolution, PVTSolutionType,
+};
 
 mod filter;
+mod state;
+
+pub(crate) mod velocity;
 
 pub use filter::{Filter, FilterState};
+pub use state::{StateKind, StateLayout};
+
+use solutions::enu_rotation;
+use solutions::validator::Validator as SolutionValidator;
 
-use log::{debug, error};
+use log::{debug, error, warn};
 use std::collections::HashMap;
 
 use crate::{
     ambiguity::Ambiguities,
     bias::{Bias, IonosphereBias, RuntimeParam as BiasRuntimeParams, TropoModel, TroposphereBias},
     candidate::Candidate,
-    cfg::Config,
-    prelude::{Error, Method, SV},
+    cfg::{time_system, Config, InterFrequencyBias, Observable, RaimOpts},
+    prelude::{Epoch, Error, Method, SV},
 };
 
-use nalgebra::{
-    base::dimension::{U4, U8},
-    OMatrix, OVector,
-};
+use map_3d::{deg2rad, ecef2geodetic, Ellipsoid};
+use nalgebra::{DMatrix, DVector, Matrix4, Vector3};
 
 use nyx::cosmic::SPEED_OF_LIGHT;
 
@@ -36,21 +43,44 @@ pub struct SVInput {
     pub tropo_bias: Bias,
 }
 
+/// Describes how one observation (row) depends on the position,
+/// so it can be linearized around any point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Linearization {
+    /// Observation does not depend on the position
+    Fixed,
+    /// Range observation: SV position [m ECEF] and
+    /// observation corrected from all models [m]
+    Range { sv: Vector3<f64>, obs: f64 },
+    /// Altitude pseudo observation [m]
+    Altitude(f64),
+}
+
 /// Navigation Input
 #[derive(Debug, Clone)]
 pub struct Input {
-    /// Measurement vector
-    pub y: OVector<f64, U8>,
-    /// NAV Matrix
-    pub g: OMatrix<f64, U8, U8>,
+    /// Measurement vector, one row per observation
+    pub y: DVector<f64>,
+    /// NAV Matrix, one row per observation
+    /// and one column per state, as described by the [StateLayout]
+    pub g: DMatrix<f64>,
     /// Weight Diagonal Matrix
-    pub w: OMatrix<f64, U8, U8>,
+    pub w: DMatrix<f64>,
     /// SV dependent data
     pub sv: HashMap<SV, SVInput>,
+    /// SV contributing to each observation (row).
+    /// Pseudo observations are not tied to any SV.
+    pub rows: Vec<Option<SV>>,
+    /// State vector layout
+    pub layout: StateLayout,
+    /// Linearization point [m ECEF]
+    pub(crate) apriori: Vector3<f64>,
+    /// Position dependency of each observation (row)
+    pub(crate) lin: Vec<Linearization>,
 }
 
 /// Navigation Output
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Output {
     /// Time Dilution of Precision
     pub tdop: f64,
@@ -59,94 +89,146 @@ pub struct Output {
     /// Position Dilution of Precision
     pub pdop: f64,
     /// Q covariance matrix
-    pub q: OMatrix<f64, U8, U8>,
+    pub q: DMatrix<f64>,
+    /// Position innovation [m], for filters that have memory
+    pub innovation: Option<f64>,
+    /// Number of iterations (linearizations)
+    pub iterations: usize,
+    /// Norm of the last position correction [m]: last iteration step (LSQ)
+    /// or measurement update applied to the predicted state (KF)
+    pub correction: f64,
     /// Filter state
     pub state: FilterState,
 }
 
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            tdop: 0.0,
+            gdop: 0.0,
+            pdop: 0.0,
+            q: DMatrix::<f64>::zeros(0, 0),
+            innovation: None,
+            iterations: 0,
+            correction: 0.0,
+            state: FilterState::default(),
+        }
+    }
+}
+
 impl Output {
-    pub(crate) fn q_covar4x4(&self) -> OMatrix<f64, U4, U4> {
-        OMatrix::<f64, U4, U4>::new(
-            self.q[(0, 0)],
-            self.q[(0, 1)],
-            self.q[(0, 2)],
-            self.q[(0, 3)],
-            self.q[(1, 0)],
-            self.q[(1, 1)],
-            self.q[(1, 2)],
-            self.q[(1, 3)],
-            self.q[(2, 0)],
-            self.q[(2, 1)],
-            self.q[(2, 2)],
-            self.q[(2, 3)],
-            self.q[(3, 0)],
-            self.q[(3, 1)],
-            self.q[(3, 2)],
-            self.q[(3, 3)],
-        )
+    /*
+     * Position and clock offset terms of the Q matrix,
+     * unresolved terms are null.
+     */
+    pub(crate) fn q_covar4x4(&self) -> Matrix4<f64> {
+        let layout = self.state.layout();
+        let kinds = [
+            StateKind::PositionX,
+            StateKind::PositionY,
+            StateKind::PositionZ,
+            StateKind::ClockOffset,
+        ];
+        let mut q = Matrix4::<f64>::zeros();
+        for (i, kind_i) in kinds.iter().enumerate() {
+            for (j, kind_j) in kinds.iter().enumerate() {
+                if let (Some(index_i), Some(index_j)) =
+                    (layout.index(*kind_i), layout.index(*kind_j))
+                {
+                    q[(i, j)] = self.q[(index_i, index_j)];
+                }
+            }
+        }
+        q
     }
 }
 
 impl Input {
-    /// Forms new Navigation Input
+    /// Forms new Navigation Input.
+    /// Every candidate contributes one code observation (per row),
+    /// and [Method::PPP] adds one phase observation per candidate.
+    /// Fixed altitude is expressed as a pseudo observation appended to the observations.
     pub fn new(
         apriori: (f64, f64, f64),
         apriori_geo: (f64, f64, f64),
         cfg: &Config,
         cd: &[Candidate],
-        w: OMatrix<f64, U8, U8>,
         ambiguities: &Ambiguities,
         iono_bias: &IonosphereBias,
         tropo_bias: &TroposphereBias,
     ) -> Result<Self, Error> {
-        let mut y = OVector::<f64, U8>::zeros();
-        let mut g = OMatrix::<f64, U8, U8>::zeros();
-        let mut sv = HashMap::<SV, SVInput>::with_capacity(cd.len());
+        let nb_cd = cd.len();
+        let layout = StateLayout::new(cfg, cd);
+        let time_only = cfg.sol_type == PVTSolutionType::TimeOnly;
+
+        let nb_obs = match cfg.method {
+            Method::PPP => 2 * nb_cd,
+            Method::SPP | Method::CPP => nb_cd,
+        };
+
+        let nb_aiding = if !time_only && cfg.fixed_altitude.is_some() {
+            1
+        } else {
+            0
+        };
+
+        let nrows = nb_obs + nb_aiding;
+        let y = DVector::<f64>::zeros(nrows);
+        let mut g = DMatrix::<f64>::zeros(nrows, layout.len());
+        let mut sv = HashMap::<SV, SVInput>::with_capacity(nb_cd);
+        let mut rows = vec![None; nrows];
+        let mut lin = vec![Linearization::Fixed; nrows];
+        let mut variances = vec![1.0_f64; nrows];
         /*
-         * Compensate for ARP (if possible)
+         * Compensate for ARP (if possible): marker to ARP eccentricity,
+         * rotated from the local ENU frame to ECEF
          */
         let apriori = match cfg.arp_enu {
-            Some(offset) => (
-                apriori.0 + offset.0,
-                apriori.1 + offset.1,
-                apriori.2 + offset.2,
-            ),
+            Some((east, north, up)) => {
+                let (lat, lon, _) =
+                    ecef2geodetic(apriori.0, apriori.1, apriori.2, Ellipsoid::WGS84);
+                let offset = enu_rotation(lat, lon) * Vector3::new(east, north, up);
+                (
+                    apriori.0 + offset[0],
+                    apriori.1 + offset[1],
+                    apriori.2 + offset[2],
+                )
+            },
             None => apriori,
         };
 
         let (x0, y0, z0) = apriori;
 
-        for i in 0..8 {
+        for (i, cd) in cd.iter().enumerate() {
             let mut sv_input = SVInput::default();
 
-            let index = if i >= cd.len() {
-                if cfg.sol_type == PVTSolutionType::TimeOnly {
-                    0
-                } else {
-                    i - cd.len()
-                }
-            } else {
-                i
-            };
-
-            let state = cd[index].state.ok_or(Error::UnresolvedState)?;
-            let clock_corr = cd[index].clock_corr.to_seconds();
+            let state = cd.state.ok_or(Error::UnresolvedState)?;
+            let clock_corr = cd.clock_corr.to_seconds();
 
             let (azimuth, elevation) = (state.azimuth, state.elevation);
             sv_input.azimuth = azimuth;
             sv_input.elevation = elevation;
 
-            let (sv_x, sv_y, sv_z) = (state.position[0], state.position[1], state.position[2]);
-            let rho = ((sv_x - x0).powi(2) + (sv_y - y0).powi(2) + (sv_z - z0).powi(2)).sqrt();
-            let (x_i, y_i, z_i) = ((x0 - sv_x) / rho, (y0 - sv_y) / rho, (z0 - sv_z) / rho);
-
-            g[(i, 0)] = x_i;
-            g[(i, 1)] = y_i;
-            g[(i, 2)] = z_i;
-            g[(i, 3)] = 1.0_f64;
-
             let mut models = 0.0_f64;
 
+            if let Some(index) = layout.index(StateKind::ClockOffset) {
+                g[(i, index)] = 1.0_f64;
+            }
+            match layout.inter_system_bias(cd.sv.constellation) {
+                Some(index) => g[(i, index)] = 1.0_f64,
+                None => {
+                    if let Some(offset) = cfg.system_time_offset(cd.sv.constellation, cd.t) {
+                        // broadcast system time offset
+                        models -= offset * SPEED_OF_LIGHT;
+                    }
+                },
+            }
+            if let Some(index) = layout.index(StateKind::TropoZwd) {
+                // residual wet delay, mapped to slant
+                g[(i, index)] =
+                    1.001_f64 / (0.002001_f64 + deg2rad(elevation).sin().powi(2)).sqrt();
+            }
+
             if cfg.modeling.sv_clock_bias {
                 models -= clock_corr * SPEED_OF_LIGHT;
             }
@@ -154,18 +236,18 @@ impl Input {
                 models -= delay * SPEED_OF_LIGHT;
             }
 
-            let (pr, frequency) = match cfg.method {
+            let (pr, frequency, snr, signals) = match cfg.method {
                 Method::SPP => {
-                    let pr = cd[index]
-                        .prefered_pseudorange()
-                        .ok_or(Error::MissingPseudoRange)?;
-                    (pr.value, pr.carrier.frequency())
+                    let pr = cd.prefered_pseudorange().ok_or(Error::MissingPseudoRange)?;
+                    (pr.value, pr.carrier.frequency(), pr.snr, (pr.carrier, None))
                 },
                 Method::CPP | Method::PPP => {
-                    let pr = cd[index]
+                    let pr = cd
                         .code_if_combination()
                         .ok_or(Error::PseudoRangeCombination)?;
-                    (pr.value, pr.reference.frequency())
+                    let snr = cd.l1_pseudorange().and_then(|pr| pr.snr);
+                    let signals = (pr.reference, Some(pr.lhs));
+                    (pr.value, pr.reference.frequency(), snr, signals)
                 },
             };
 
@@ -176,11 +258,27 @@ impl Input {
                 }
             }
 
+            // Glonass code inter frequency bias
+            if let Some(channel) = cd.fdma_channel() {
+                match &cfg.glonass_ifb {
+                    InterFrequencyBias::Estimated => {
+                        if let Some(index) = layout.index(StateKind::InterFrequencyBias) {
+                            g[(i, index)] = channel as f64;
+                        }
+                    },
+                    InterFrequencyBias::Known(biases) => {
+                        if let Some(ifb) = biases.iter().find(|ifb| ifb.channel == channel) {
+                            models += ifb.bias;
+                        }
+                    },
+                }
+            }
+
             /*
              * IONO + TROPO biases
              */
             let rtm = BiasRuntimeParams {
-                t: cd[index].t,
+                t: cd.t,
                 elevation,
                 azimuth,
                 frequency,
@@ -193,17 +291,11 @@ impl Input {
             if cfg.modeling.tropo_delay {
                 if tropo_bias.needs_modeling() {
                     let bias = TroposphereBias::model(TropoModel::Niel, &rtm);
-                    debug!(
-                        "{}({}): modeled tropo delay {:.3E}[m]",
-                        cd[index].t, cd[index].sv, bias
-                    );
+                    debug!("{}({}): modeled tropo delay {:.3E}[m]", cd.t, cd.sv, bias);
                     models += bias;
                     sv_input.tropo_bias = Bias::modeled(bias);
                 } else if let Some(bias) = tropo_bias.bias(&rtm) {
-                    debug!(
-                        "{}({}): measured tropo delay {:.3E}[m]",
-                        cd[index].t, cd[index].sv, bias
-                    );
+                    debug!("{}({}): measured tropo delay {:.3E}[m]", cd.t, cd.sv, bias);
                     models += bias;
                     sv_input.tropo_bias = Bias::measured(bias);
                 }
@@ -216,85 +308,370 @@ impl Input {
                 if let Some(bias) = iono_bias.bias(&rtm) {
                     debug!(
                         "{} : modeled iono delay (f={:.3E}Hz) {:.3E}[m]",
-                        cd[index].t, rtm.frequency, bias
+                        cd.t, rtm.frequency, bias
                     );
                     models += bias;
                     sv_input.iono_bias = Bias::modeled(bias);
                 }
             }
 
-            y[i] = pr - rho - models;
-
-            if i > 3 {
-                g[(i, i)] = 1.0_f64;
-
-                if cfg.method == Method::PPP {
-                    let cmb = cd[index]
-                        .phase_if_combination()
-                        .ok_or(Error::PseudoRangeCombination)?;
-
-                    let f_1 = cmb.reference.frequency();
-                    let lambda_j = cmb.lhs.wavelength();
-                    let f_j = cmb.lhs.frequency();
-
-                    let (lambda_n, lambda_w) =
-                        (SPEED_OF_LIGHT / (f_1 + f_j), SPEED_OF_LIGHT / (f_1 - f_j));
-
-                    let bias =
-                        if let Some(ambiguity) = ambiguities.get(&(cd[index].sv, cmb.reference)) {
-                            let (n_1, n_w) = (ambiguity.n_1, ambiguity.n_w);
-                            let b_c = lambda_n * (n_1 + (lambda_w / lambda_j) * n_w);
-                            debug!(
-                                "{} ({}/{}) b_c: {}",
-                                cd[index].t, cd[index].sv, cmb.reference, b_c
-                            );
-                            b_c
-                        } else {
-                            error!(
-                                "{} ({}/{}): unresolved ambiguity",
-                                cd[index].t, cd[index].sv, cmb.reference
-                            );
-                            return Err(Error::UnresolvedAmbiguity);
-                        };
+            // satellite (when positions refer to the CoM) and receiver antenna phase centers
+            let sv_pc = cd.sv_phase_center(signals.0, signals.1);
+            let rx_pc = cd.rx_phase_center(signals.0, signals.1);
+            lin[i] = Linearization::Range {
+                sv: state.position + sv_pc.offset - rx_pc.offset,
+                obs: pr - models - sv_pc.variation - rx_pc.variation,
+            };
+            rows[i] = Some(cd.sv);
+            variances[i] = cfg.solver.observation_variance(
+                cd.sv.constellation,
+                Observable::PseudoRange,
+                elevation,
+                snr,
+                cd.code_variance,
+            );
+
+            if cfg.method == Method::PPP {
+                let j = nb_cd + i;
+                let cmb = cd
+                    .phase_if_combination()
+                    .ok_or(Error::PhaseRangeCombination)?;
+
+                let f_1 = cmb.reference.frequency();
+                let lambda_j = cmb.lhs.wavelength();
+                let f_j = cmb.lhs.frequency();
+
+                let (lambda_n, lambda_w) =
+                    (SPEED_OF_LIGHT / (f_1 + f_j), SPEED_OF_LIGHT / (f_1 - f_j));
+
+                let bias = if cfg.float_ambiguities() {
+                    // float ambiguity, estimated by the filter
+                    let arc = cd.phase_arc.ok_or(Error::UnresolvedAmbiguity)?;
+                    arc.apriori
+                } else if let Some(ambiguity) = ambiguities.get(&(cd.sv, cmb.reference)) {
+                    let (n_1, n_w) = (ambiguity.n_1, ambiguity.n_w);
+                    let b_c = lambda_n * (n_1 + (lambda_w / lambda_j) * n_w);
+                    debug!("{} ({}/{}) b_c: {}", cd.t, cd.sv, cmb.reference, b_c);
+                    b_c
+                } else {
+                    error!(
+                        "{} ({}/{}): unresolved ambiguity",
+                        cd.t, cd.sv, cmb.reference
+                    );
+                    return Err(Error::UnresolvedAmbiguity);
+                };
 
-                    // TODO: conclude windup
-                    let windup = 0.0_f64;
+                // phase wind-up [cycles], ionosphere free combination
+                let windup = if cfg.modeling.phase_windup {
+                    lambda_n * cd.wind_up
+                } else {
+                    0.0_f64
+                };
 
-                    y[i] = cmb.value - rho - models - windup + bias;
+                for k in 0..layout.len() {
+                    g[(j, k)] = g[(i, k)];
+                }
+                if let Some(index) = layout.index(StateKind::InterFrequencyBias) {
+                    // code bias only
+                    g[(j, index)] = 0.0_f64;
                 }
+                if let Some(index) = layout.ambiguity(cd.sv) {
+                    g[(j, index)] = 1.0_f64;
+                }
+                let sv_pc = cd.sv_phase_center(cmb.reference, Some(cmb.lhs));
+                let rx_pc = cd.rx_phase_center(cmb.reference, Some(cmb.lhs));
+                lin[j] = Linearization::Range {
+                    sv: state.position + sv_pc.offset - rx_pc.offset,
+                    obs: cmb.value - models - windup - bias - sv_pc.variation - rx_pc.variation,
+                };
+                rows[j] = Some(cd.sv);
+                variances[j] = cfg.solver.observation_variance(
+                    cd.sv.constellation,
+                    Observable::PhaseRange,
+                    elevation,
+                    cd.l1_phaserange().and_then(|ph| ph.snr),
+                    cd.phase_variance,
+                );
             }
 
-            if i < cd.len() {
-                sv.insert(cd[i].sv, sv_input);
+            sv.insert(cd.sv, sv_input);
+        }
+
+        if nb_aiding > 0 {
+            if let Some(altitude) = cfg.fixed_altitude {
+                lin[nb_obs] = Linearization::Altitude(altitude);
             }
         }
 
-        debug!("y: {} g: {}, w: {}", y, g, w);
-        Ok(Self { y, g, w, sv })
+        let w = DMatrix::<f64>::from_diagonal(&DVector::from_vec(
+            variances.iter().map(|var| 1.0 / var).collect(),
+        ));
+
+        let mut input = Self {
+            y,
+            g,
+            w,
+            sv,
+            rows,
+            layout,
+            apriori: Vector3::new(x0, y0, z0),
+            lin,
+        };
+        input.linearize();
+        debug!("y: {} g: {}, w: {}", input.y, input.g, input.w);
+        Ok(input)
+    }
+    /*
+     * Evaluates observations and position dependent terms
+     * of the navigation matrix, at the linearization point
+     */
+    fn linearize(&mut self) {
+        let apriori = self.apriori;
+        for (i, lin) in self.lin.iter().enumerate() {
+            match lin {
+                Linearization::Fixed => {},
+                Linearization::Range { sv, obs } => {
+                    let rho = (sv - apriori).norm();
+                    let los = (apriori - sv) / rho;
+                    for (kind, dx) in StateLayout::POSITION.iter().zip(los.iter()) {
+                        if let Some(index) = self.layout.index(*kind) {
+                            self.g[(i, index)] = *dx;
+                        }
+                    }
+                    self.y[i] = obs - rho;
+                },
+                Linearization::Altitude(altitude) => {
+                    // altitude aiding: constrain the vertical component
+                    let (lat, lon, h) =
+                        ecef2geodetic(apriori[0], apriori[1], apriori[2], Ellipsoid::WGS84);
+                    let up = [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()];
+                    for (kind, up) in StateLayout::POSITION.iter().zip(up) {
+                        if let Some(index) = self.layout.index(*kind) {
+                            self.g[(i, index)] = up;
+                        }
+                    }
+                    self.y[i] = altitude - h;
+                },
+            }
+        }
+    }
+    /// Returns a copy of Self, linearized around apriori + dx [m ECEF]
+    pub(crate) fn relinearize(&self, dx: &Vector3<f64>) -> Self {
+        let mut input = self.clone();
+        input.apriori += dx;
+        input.linearize();
+        input
+    }
+    /// Returns number of SV contributing to Self
+    pub(crate) fn nb_sv(&self) -> usize {
+        self.sv.len()
+    }
+    /// Returns a copy of Self, without any observation of given SV,
+    /// nor the states only this SV was observing
+    pub(crate) fn without(&self, sv: SV) -> Self {
+        let indices = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter_map(|(i, row)| if *row == Some(sv) { Some(i) } else { None })
+            .collect::<Vec<_>>();
+        let g = self.g.clone().remove_rows_at(&indices);
+        // states only this SV observed (its inter system bias, its ambiguity..)
+        // become unobservable: they are removed, the other columns are kept as is
+        let mut unobserved = (0..g.ncols())
+            .filter(|j| {
+                self.g.column(*j).iter().any(|g_ij| *g_ij != 0.0)
+                    && g.column(*j).iter().all(|g_ij| *g_ij == 0.0)
+            })
+            .collect::<Vec<_>>();
+        // this SV was the last one of the reference system time, and all remaining
+        // system times are estimated: the first one becomes the reference
+        // (as in StateLayout::new) and its bias is absorbed by the clock offset
+        let mut systems = self
+            .sv
+            .keys()
+            .filter(|k| **k != sv)
+            .map(|k| time_system(k.constellation))
+            .collect::<Vec<_>>();
+        systems.sort();
+        systems.dedup();
+        let mut reference = self.layout.reference();
+        if !systems.is_empty() && !systems.contains(&reference) {
+            let biases = systems
+                .iter()
+                .map(|system| self.layout.index(StateKind::InterSystemBias(*system)))
+                .collect::<Option<Vec<_>>>();
+            if let Some(biases) = biases {
+                reference = systems[0];
+                unobserved.push(biases[0]);
+                unobserved.sort();
+            }
+        }
+        Self {
+            y: self.y.clone().remove_rows_at(&indices),
+            g: g.remove_columns_at(&unobserved),
+            w: self
+                .w
+                .clone()
+                .remove_rows_at(&indices)
+                .remove_columns_at(&indices),
+            sv: self
+                .sv
+                .iter()
+                .filter_map(|(k, v)| {
+                    if *k != sv {
+                        Some((*k, v.clone()))
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            rows: self
+                .rows
+                .iter()
+                .copied()
+                .filter(|row| *row != Some(sv))
+                .collect(),
+            layout: self.layout.without(&unobserved, reference),
+            apriori: self.apriori,
+            lin: self
+                .lin
+                .iter()
+                .zip(self.rows.iter())
+                .filter_map(|(lin, row)| if *row != Some(sv) { Some(*lin) } else { None })
+                .collect(),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct Navigation {
-    filter: Filter,
-    pending: Output,
-    filter_state: Option<FilterState>,
+    cfg: Config,
+    pending: (Epoch, Output),
+    filter_state: Option<(Epoch, FilterState)>,
 }
 
 impl Navigation {
-    pub fn new(filter: Filter) -> Self {
+    pub fn new(cfg: &Config) -> Self {
         Self {
-            filter,
+            cfg: cfg.clone(),
             filter_state: None,
             pending: Default::default(),
         }
     }
-    pub fn resolve(&mut self, input: &Input) -> Result<Output, Error> {
-        let out = self.filter.resolve(input, self.filter_state.clone())?;
-        self.pending = out.clone();
+    pub fn resolve(&mut self, t: Epoch, input: &Input) -> Result<Output, Error> {
+        // previous state, projected onto current layout
+        let (p_state, dt) = match &self.filter_state {
+            Some((p_t, state)) => (Some(state.remap(&input.layout)), (t - *p_t).to_seconds()),
+            None => (None, 0.0_f64),
+        };
+        let out = self
+            .cfg
+            .solver
+            .filter
+            .resolve(input, p_state, &self.cfg, dt)?;
+        self.pending = (t, out.clone());
         Ok(out)
     }
+    /*
+     * Resolves this epoch, with possible RAIM fault detection and exclusion:
+     * while the residuals test fails, the SV with the largest normalized residual
+     * is excluded and the epoch is resolved again. Returns the (reduced) input,
+     * the output and the excluded SV.
+     */
+    pub(crate) fn resolve_fde(
+        &mut self,
+        t: Epoch,
+        mut input: Input,
+        raim: Option<&RaimOpts>,
+        min_required: usize,
+    ) -> Result<(Input, Output, Vec<SV>), Error> {
+        let mut excluded = Vec::<SV>::new();
+        loop {
+            let output = self.resolve(t, &input)?;
+            let raim = match raim {
+                Some(raim) => raim,
+                None => return Ok((input, output, excluded)),
+            };
+            let validator = SolutionValidator::new(&input, &output);
+            match validator.raim(raim) {
+                Ok(_) => return Ok((input, output, excluded)),
+                Err(cause) => {
+                    let exclusion =
+                        if excluded.len() < raim.max_exclusions && input.nb_sv() > min_required {
+                            validator.worst_sv()
+                        } else {
+                            None
+                        };
+                    if let Some(sv) = exclusion {
+                        warn!("{} ({}): raim exclusion - {}", t, sv, cause);
+                        input = input.without(sv);
+                        excluded.push(sv);
+                    } else {
+                        error!("{} - raim failure - {}", t, cause);
+                        return Err(Error::InvalidatedSolution(cause));
+                    }
+                },
+            }
+        }
+    }
     pub fn validate(&mut self) {
-        self.filter_state = Some(self.pending.state.clone());
+        let (t, output) = &self.pending;
+        self.filter_state = Some((*t, output.state.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Navigation, StateKind, StateLayout};
+    use crate::{
+        cfg::RaimOpts,
+        navigation::InvalidationCause,
+        prelude::{Config, Epoch, Error, Method, SV},
+        tests::geometry,
+    };
+    #[test]
+    fn raim_exclusion() {
+        let cfg = Config::static_preset(Method::SPP);
+        let layout = StateLayout::new(&cfg, &[]);
+
+        let clock = 15.0_f64;
+        let vehicles = geometry::vehicles(8);
+        // one faulty vehicle
+        let (faulty, bias) = (vehicles[3].0, 100.0_f64);
+
+        let nrows = vehicles.len();
+        let build = || {
+            let offset = |sv: SV| if sv == faulty { clock + bias } else { clock };
+            geometry::range_input(&layout, &vehicles, offset, &geometry::rx())
+        };
+
+        let raim = RaimOpts::default();
+        let mut nav = Navigation::new(&cfg);
+        let (input, output, excluded) = nav
+            .resolve_fde(Epoch::default(), build(), Some(&raim), 4)
+            .unwrap();
+        assert_eq!(excluded, vec![faulty]);
+        assert_eq!(input.nb_sv(), nrows - 1);
+        assert!(!input.sv.contains_key(&faulty));
+        for kind in StateLayout::POSITION {
+            assert!(output.state.value(kind).unwrap().abs() < 1.0E-3);
+        }
+        let estimate = output.state.value(StateKind::ClockOffset).unwrap();
+        assert!((estimate - clock).abs() < 1.0E-3);
+
+        // no exclusion allowed: the fault is detected, the epoch is rejected
+        let raim = RaimOpts {
+            max_exclusions: 0,
+            ..Default::default()
+        };
+        let mut nav = Navigation::new(&cfg);
+        let result = nav.resolve_fde(Epoch::default(), build(), Some(&raim), 4);
+        assert!(matches!(
+            result,
+            Err(Error::InvalidatedSolution(InvalidationCause::CodeResidual(
+                _
+            )))
+        ));
     }
 }
@@ -0,0 +1,310 @@
+//! Advanced RAIM (ARAIM) Protection Levels
+use log::debug;
+use nalgebra::{DMatrix, Matrix3};
+use std::collections::HashSet;
+
+use super::enu_rotation;
+use crate::{
+    cfg::IntegrityOpts,
+    navigation::{Input, StateLayout},
+    stats::{normal_q, normal_q_inv},
+};
+
+/*
+ * Ranging error model of one observation
+ */
+struct RangeError {
+    // variance used for integrity [m^2]
+    var_int: f64,
+    // variance used for accuracy and continuity [m^2]
+    var_acc: f64,
+    // maximal nominal bias [m]
+    b_nom: f64,
+}
+
+/*
+ * Weighted least squares projection onto local (East, North, Up) axes:
+ * 3 x n matrix, with null columns for the excluded observation.
+ * Returns None when the (sub) geometry does not allow resolving the position.
+ */
+fn enu_projection(
+    g: &DMatrix<f64>,
+    errors: &[RangeError],
+    layout: &StateLayout,
+    r: &Matrix3<f64>,
+    excluded: Option<usize>,
+) -> Option<DMatrix<f64>> {
+    let n = g.nrows();
+    let rows = (0..n).filter(|i| Some(*i) != excluded).collect::<Vec<_>>();
+    // states that are no longer observed are dropped
+    let cols = (0..g.ncols())
+        .filter(|j| rows.iter().any(|i| g[(*i, *j)] != 0.0))
+        .collect::<Vec<_>>();
+
+    let mut g_k = DMatrix::<f64>::zeros(rows.len(), cols.len());
+    let mut w_k = DMatrix::<f64>::zeros(rows.len(), rows.len());
+    for (ii, i) in rows.iter().enumerate() {
+        for (jj, j) in cols.iter().enumerate() {
+            g_k[(ii, jj)] = g[(*i, *j)];
+        }
+        w_k[(ii, ii)] = 1.0 / errors[*i].var_int;
+    }
+    let g_prime = g_k.transpose();
+    let s = (&g_prime * &w_k * &g_k).try_inverse()? * g_prime * w_k;
+
+    let mut s_enu = DMatrix::<f64>::zeros(3, n);
+    for (c, kind) in StateLayout::POSITION.iter().enumerate() {
+        let jj = cols.iter().position(|j| layout.kind(*j) == Some(*kind))?;
+        for (ii, i) in rows.iter().enumerate() {
+            for axis in 0..3 {
+                s_enu[(axis, *i)] += r[(c, axis)] * s[(jj, ii)];
+            }
+        }
+    }
+    Some(s_enu)
+}
+
+/*
+ * Protection level along one axis: solves
+ * 2 Q((PL - b0)/s0) + sum_k p_k Q((PL - T_k - b_k)/s_k) = PHMI
+ */
+fn axis_protection_level(
+    axis: usize,
+    s_0: &DMatrix<f64>,
+    s_k: &[DMatrix<f64>],
+    errors: &[RangeError],
+    phmi: f64,
+    p_fa: f64,
+    p_sat: f64,
+) -> Option<f64> {
+    let sigma = |s: &DMatrix<f64>| -> f64 {
+        s.row(axis)
+            .iter()
+            .zip(errors.iter())
+            .map(|(s_i, e)| s_i.powi(2) * e.var_int)
+            .sum::<f64>()
+            .sqrt()
+    };
+    let bias = |s: &DMatrix<f64>| -> f64 {
+        s.row(axis)
+            .iter()
+            .zip(errors.iter())
+            .map(|(s_i, e)| s_i.abs() * e.b_nom)
+            .sum::<f64>()
+    };
+
+    let k_fa = normal_q_inv(p_fa / (2.0 * s_k.len() as f64));
+    let (sigma_0, b_0) = (sigma(s_0), bias(s_0));
+    let modes = s_k
+        .iter()
+        .map(|s| {
+            let sigma_ss = (s - s_0)
+                .row(axis)
+                .iter()
+                .zip(errors.iter())
+                .map(|(ds_i, e)| ds_i.powi(2) * e.var_acc)
+                .sum::<f64>()
+                .sqrt();
+            (sigma(s), k_fa * sigma_ss + bias(s))
+        })
+        .collect::<Vec<_>>();
+
+    let risk = |pl: f64| -> f64 {
+        let mut risk = 2.0 * normal_q((pl - b_0) / sigma_0);
+        for (sigma_k, offset_k) in modes.iter() {
+            risk += p_sat * normal_q((pl - offset_k) / sigma_k);
+        }
+        risk
+    };
+
+    if sigma_0 <= 0.0 || modes.iter().any(|(sigma_k, _)| *sigma_k <= 0.0) {
+        return None;
+    }
+
+    let mut lower = 0.0_f64;
+    let mut upper = modes
+        .iter()
+        .map(|(sigma_k, offset_k)| offset_k + 40.0 * sigma_k)
+        .fold(b_0 + 40.0 * sigma_0, f64::max);
+    if risk(lower) <= phmi {
+        return Some(lower);
+    }
+    for _ in 0..100 {
+        let mid = (lower + upper) / 2.0;
+        if risk(mid) > phmi {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+    Some(upper)
+}
+
+/*
+ * Horizontal and Vertical Protection Levels [m], considering
+ * the fault free hypothesis and every single satellite fault.
+ * (lat, lon): apriori position [rad].
+ */
+pub(crate) fn protection_levels(
+    input: &Input,
+    lat: f64,
+    lon: f64,
+    opts: &IntegrityOpts,
+) -> (Option<f64>, Option<f64>) {
+    if !input.layout.has_position() {
+        return (None, None);
+    }
+
+    // one code observation per SV
+    let mut sv_rows = Vec::<usize>::new();
+    let mut seen = HashSet::new();
+    for (i, sv) in input.rows.iter().enumerate() {
+        if let Some(sv) = sv {
+            if seen.insert(*sv) {
+                sv_rows.push(i);
+            }
+        }
+    }
+
+    let n = sv_rows.len();
+    let mut g = DMatrix::<f64>::zeros(n, input.g.ncols());
+    let mut errors = Vec::<RangeError>::with_capacity(n);
+    for (ii, i) in sv_rows.iter().enumerate() {
+        g.set_row(ii, &input.g.row(*i));
+        let sv = input.rows[*i].unwrap();
+        let elev = input
+            .sv
+            .get(&sv)
+            .map(|sv_input| sv_input.elevation.to_radians())
+            .unwrap_or(0.0);
+        let var_tropo = (opts.sigma_tropo * 1.001 / (0.002001 + elev.sin().powi(2)).sqrt()).powi(2);
+        let var_user = opts.sigma_user.powi(2);
+        let model = opts.range_error(sv.constellation);
+        errors.push(RangeError {
+            var_int: model.ura.powi(2) + var_tropo + var_user,
+            var_acc: model.ure.powi(2) + var_tropo + var_user,
+            b_nom: model.b_nom,
+        });
+    }
+
+    let r = enu_rotation(lat, lon);
+    let s_0 = match enu_projection(&g, &errors, &input.layout, &r, None) {
+        Some(s_0) => s_0,
+        None => return (None, None),
+    };
+
+    // fault modes that cannot be monitored consume the integrity budget
+    let mut s_k = Vec::<DMatrix<f64>>::with_capacity(n);
+    let mut p_unmonitored = 0.0_f64;
+    for k in 0..n {
+        match enu_projection(&g, &errors, &input.layout, &r, Some(k)) {
+            Some(s) => s_k.push(s),
+            None => p_unmonitored += opts.p_sat,
+        }
+    }
+
+    let pl = |axis: usize, phmi: f64, p_fa: f64| -> Option<f64> {
+        let phmi = phmi - p_unmonitored;
+        if phmi <= 0.0 || s_k.is_empty() {
+            return None;
+        }
+        axis_protection_level(axis, &s_0, &s_k, &errors, phmi, p_fa, opts.p_sat)
+    };
+
+    // horizontal budget is evenly split between East and North
+    let hpl = match (
+        pl(0, opts.phmi_horz / 2.0, opts.p_fa_horz / 2.0),
+        pl(1, opts.phmi_horz / 2.0, opts.p_fa_horz / 2.0),
+    ) {
+        (Some(pl_e), Some(pl_n)) => Some((pl_e.powi(2) + pl_n.powi(2)).sqrt()),
+        _ => None,
+    };
+    let vpl = pl(2, opts.phmi_vert, opts.p_fa_vert);
+    debug!("hpl={:?} vpl={:?}", hpl, vpl);
+    (hpl, vpl)
+}
+
+#[cfg(test)]
+mod test {
+    use super::protection_levels;
+    use crate::{
+        cfg::{IntegrityOpts, RangeErrorModel},
+        navigation::{Input, SVInput, StateLayout},
+        prelude::{Config, Constellation, Method, SV},
+        tests::geometry,
+    };
+    use nalgebra::{DMatrix, DVector};
+    use std::collections::HashMap;
+    fn input(nb_sv: usize) -> Input {
+        let layout = StateLayout::new(&Config::static_preset(Method::SPP), &[]);
+        let mut g = DMatrix::<f64>::zeros(nb_sv, layout.len());
+        let mut sv = HashMap::new();
+        let mut rows = Vec::new();
+        // station on the equator, at 0° longitude: local (E, N, U) is ECEF (Y, Z, X)
+        for i in 0..nb_sv {
+            let (elev, azim) = (
+                (20.0 + 60.0 * (i % 3) as f64 / 2.0).to_radians(),
+                (360.0 * i as f64 / nb_sv as f64).to_radians(),
+            );
+            let (e, n, u) = (elev.cos() * azim.sin(), elev.cos() * azim.cos(), elev.sin());
+            g[(i, 0)] = -u;
+            g[(i, 1)] = -e;
+            g[(i, 2)] = -n;
+            g[(i, 3)] = 1.0;
+            let id = SV::new(Constellation::GPS, i as u8 + 1);
+            sv.insert(
+                id,
+                SVInput {
+                    elevation: elev.to_degrees(),
+                    azimuth: azim.to_degrees(),
+                    ..Default::default()
+                },
+            );
+            rows.push(Some(id));
+        }
+        Input {
+            sv,
+            rows,
+            ..geometry::fixed_input(&layout, g, DVector::<f64>::zeros(nb_sv))
+        }
+    }
+    #[test]
+    fn protection_levels_geometry() {
+        let opts = IntegrityOpts::default();
+        let (hpl, vpl) = protection_levels(&input(8), 0.0, 0.0, &opts);
+        let (hpl, vpl) = (hpl.unwrap(), vpl.unwrap());
+        assert!(hpl > 0.0 && vpl > 0.0);
+
+        // more vehicles: tighter bounds
+        let (hpl_12, vpl_12) = protection_levels(&input(12), 0.0, 0.0, &opts);
+        assert!(hpl_12.unwrap() < hpl);
+        assert!(vpl_12.unwrap() < vpl);
+
+        // better ranging accuracy: tighter bounds
+        let opts = IntegrityOpts {
+            range_errors: vec![RangeErrorModel {
+                constellation: Constellation::GPS,
+                ura: 0.5,
+                ure: 0.33,
+                b_nom: 0.5,
+            }],
+            ..Default::default()
+        };
+        let (hpl_acc, vpl_acc) = protection_levels(&input(8), 0.0, 0.0, &opts);
+        assert!(hpl_acc.unwrap() < hpl);
+        assert!(vpl_acc.unwrap() < vpl);
+
+        // larger troposphere residuals: looser bounds
+        let opts_tropo = IntegrityOpts {
+            sigma_tropo: 0.5,
+            ..opts.clone()
+        };
+        let (hpl_tropo, vpl_tropo) = protection_levels(&input(8), 0.0, 0.0, &opts_tropo);
+        assert!(hpl_tropo.unwrap() > hpl_acc.unwrap());
+        assert!(vpl_tropo.unwrap() > vpl_acc.unwrap());
+
+        // not enough redundancy: no single fault can be monitored
+        let (hpl, vpl) = protection_levels(&input(4), 0.0, 0.0, &opts);
+        assert!(hpl.is_none() && vpl.is_none());
+    }
+}
@@ -1,11 +1,14 @@
 //! PVT Solutions
 use std::collections::HashMap;
 
-use crate::prelude::{Ambiguities, Carrier, Duration, TimeScale, Vector3, SV};
+use crate::prelude::{
+    Ambiguities, Carrier, Constellation, CycleSlip, Duration, TimeScale, Vector3, SV,
+};
 
 use super::SVInput;
 use nalgebra::base::{Matrix3, Matrix4};
 
+pub(crate) mod integrity;
 pub(crate) mod validator;
 pub use validator::InvalidationCause;
 
@@ -41,6 +44,23 @@ impl std::fmt::Display for PVTSolutionType {
     }
 }
 
+/// Solution obtained once (a subset of) the phase ambiguities
+/// are fixed to integers (see [crate::prelude::AmbiguityOpts]).
+#[derive(Debug, Clone)]
+pub struct FixedSolution {
+    /// Position [m] ECEF
+    pub position: Vector3<f64>,
+    /// Offset to timescale
+    pub dt: Duration,
+    /// Space Vehicles whose ambiguities were fixed. This may only be a subset
+    /// of the contributing vehicles, on partial ambiguity resolution.
+    pub sv: Vec<SV>,
+    /// Ratio test statistic of the fixed ambiguities
+    pub ratio: f64,
+    /// Bootstrapped success rate of the fixed ambiguities
+    pub success_rate: f64,
+}
+
 /// PVT Solution, always expressed as the correction to apply
 /// to an Apriori / static position.
 #[derive(Debug, Clone)]
@@ -48,25 +68,73 @@ impl std::fmt::Display for PVTSolutionType {
 pub struct PVTSolution {
     /// Position error (in [m] ECEF)
     pub position: Vector3<f64>,
-    /// Absolute Velocity (in [m/s] ECEF).
+    /// Absolute Velocity (in [m/s] ECEF), from (by order of preference):
+    /// - instantaneous velocity, when Doppler observations were provided
+    /// - velocity states of the [crate::prelude::Filter::Kalman], for roaming receivers
+    /// - finite difference of successive positions, otherwise
     pub velocity: Vector3<f64>,
-    /// Timescale
+    /// Velocity covariance matrix (in [m²/s²] ECEF), defined when velocity
+    /// was resolved from Doppler observations or estimated by the
+    /// [crate::prelude::Filter::Kalman]. Undefined for finite differences.
+    pub velocity_covar: Option<Matrix3<f64>>,
+    /// Timescale [Self::dt] refers to
     pub timescale: TimeScale,
+    /// [Constellation] whose system time [Self::dt] refers to. This is the
+    /// one defined by the configured timescale, unless it did not contribute
+    /// to this solution (nor could be compensated for): the first contributing
+    /// system time then takes its place, and [Self::isb] is relative to it.
+    pub reference: Constellation,
     /// Offset to timescale
     pub dt: Duration,
+    /// Receiver clock drift [s/s], resolved from Doppler observations
+    /// or estimated by the [crate::prelude::Filter::Kalman] clock state.
+    pub clock_drift: Option<f64>,
+    /// Receiver clock drift standard deviation [s/s]
+    pub clock_drift_sigma: Option<f64>,
+    /// Inter System Bias of each contributing [Constellation] whose
+    /// system time is not the reference one: receiver clock offset to this
+    /// system time, minus [Self::dt]. Either estimated or broadcast
+    /// (see [crate::prelude::InterSystemBias]).
+    pub isb: HashMap<Constellation, Duration>,
+    /// Estimated Glonass code Inter Frequency Bias [m] per frequency
+    /// channel number (see [crate::prelude::InterFrequencyBias]).
+    pub glonass_ifb: Option<f64>,
     /// Space Vehicles that helped form this solution
     /// and data associated to each individual SV
     pub sv: HashMap<SV, SVInput>,
+    /// Space Vehicles that were excluded from this solution
+    /// by the RAIM fault detection and exclusion (see [crate::prelude::RaimOpts]).
+    pub excluded: Vec<SV>,
     /// Geometric Dilution of Precision
     pub gdop: f64,
     /// Time Dilution of Precision
     pub tdop: f64,
     /// Position Dilution of Precision
     pub pdop: f64,
+    /// Number of iterations (linearizations) needed to form this solution
+    pub iterations: usize,
+    /// Norm of the last position correction [m], indicates
+    /// how well the iterative process converged.
+    pub correction: f64,
+    /// Horizontal Protection Level [m], when integrity
+    /// risk allocation is defined (see [crate::prelude::IntegrityOpts]).
+    pub hpl: Option<f64>,
+    /// Vertical Protection Level [m], when integrity
+    /// risk allocation is defined (see [crate::prelude::IntegrityOpts]).
+    pub vpl: Option<f64>,
     /// Resolved ambiguities (at this point and time), per SV and signal.
     /// Ambiguities are null if navigation does not use them (see [Method]).
     /// This is useful for advanced applications that want or need this level of detail.
     pub ambiguities: Ambiguities,
+    /// Float ionosphere free ambiguities [m], per SV, estimated by the
+    /// [crate::prelude::Filter::Kalman] in [crate::prelude::Method::PPP].
+    /// Empty otherwise.
+    pub float_ambiguities: HashMap<SV, f64>,
+    /// Ambiguity fixed solution, when (a subset of) the float ambiguities
+    /// could be fixed. [Self::position] remains the float solution.
+    pub fixed: Option<FixedSolution>,
+    /// Cycle slips declared at this epoch, per SV
+    pub cycle_slips: Vec<CycleSlip>,
     // // Instrument bias, determined from Phase Range based Navigation (see [Method])
     // // and internal signal ambiguity solving. If Navigation [Method] is not based on Phase Range,
     // // the bias cannot be estimated (null). This is useful for advanced applications that want or need this level of detail.
@@ -75,23 +143,31 @@ pub struct PVTSolution {
     pub(crate) q: Matrix4<f64>,
 }
 
+/*
+ * ENU to ECEF rotation matrix: columns are the local
+ * East, North and Up unit vectors expressed in ECEF.
+ */
+pub(crate) fn enu_rotation(lat: f64, lon: f64) -> Matrix3<f64> {
+    Matrix3::<f64>::new(
+        -lon.sin(),
+        -lon.cos() * lat.sin(),
+        lat.cos() * lon.cos(),
+        lon.cos(),
+        -lat.sin() * lon.sin(),
+        lat.cos() * lon.sin(),
+        0.0_f64,
+        lat.cos(),
+        lat.sin(),
+    )
+}
+
 impl PVTSolution {
     /// Returns list of Space Vehicles (SV) that help form this solution.
     pub fn sv(&self) -> Vec<SV> {
         self.sv.keys().copied().collect()
     }
     fn q_enu(&self, lat: f64, lon: f64) -> Matrix3<f64> {
-        let r = Matrix3::<f64>::new(
-            -lon.sin(),
-            -lon.cos() * lat.sin(),
-            lat.cos() * lon.cos(),
-            lon.cos(),
-            -lat.sin() * lon.sin(),
-            lat.cos() * lon.sin(),
-            0.0_f64,
-            lat.cos(),
-            lon.sin(),
-        );
+        let r = enu_rotation(lat, lon);
         let q_3 = Matrix3::<f64>::new(
             self.q[(0, 0)],
             self.q[(0, 1)],
@@ -113,3 +189,54 @@ impl PVTSolution {
         self.q_enu(lat, lon)[(2, 2)].sqrt()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::PVTSolution;
+    use crate::prelude::{Constellation, Duration, TimeScale, Vector3};
+    use nalgebra::{Matrix3, Matrix4};
+    use std::collections::HashMap;
+    #[test]
+    fn dops() {
+        // station whose latitude and longitude differ
+        let (lat, lon) = (45.0_f64.to_radians(), 10.0_f64.to_radians());
+        let east = Vector3::new(-lon.sin(), lon.cos(), 0.0);
+        let north = Vector3::new(-lat.sin() * lon.cos(), -lat.sin() * lon.sin(), lat.cos());
+        let up = Vector3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin());
+
+        // (E, N, U) variances of 1, 4 and 9, expressed in ECEF
+        let q_xyz: Matrix3<f64> =
+            east * east.transpose() + 4.0 * north * north.transpose() + 9.0 * up * up.transpose();
+        let mut q = Matrix4::<f64>::identity();
+        q.fixed_view_mut::<3, 3>(0, 0).copy_from(&q_xyz);
+
+        let solution = PVTSolution {
+            position: Default::default(),
+            velocity: Default::default(),
+            velocity_covar: None,
+            timescale: TimeScale::GPST,
+            reference: Constellation::GPS,
+            dt: Duration::default(),
+            clock_drift: None,
+            clock_drift_sigma: None,
+            isb: HashMap::new(),
+            glonass_ifb: None,
+            sv: HashMap::new(),
+            excluded: vec![],
+            gdop: 0.0,
+            tdop: 0.0,
+            pdop: 0.0,
+            iterations: 1,
+            correction: 0.0,
+            hpl: None,
+            vpl: None,
+            ambiguities: HashMap::new(),
+            float_ambiguities: HashMap::new(),
+            fixed: None,
+            cycle_slips: vec![],
+            q,
+        };
+        assert!((solution.hdop(lat, lon) - 5.0_f64.sqrt()).abs() < 1.0E-9);
+        assert!((solution.vdop(lat, lon) - 3.0).abs() < 1.0E-9);
+    }
+}
@@ -1,11 +1,12 @@
 use log::debug;
 use nalgebra::{DVector, Vector3};
-use nyx::cosmic::SPEED_OF_LIGHT;
 use thiserror::Error;
 
 use crate::{
-    navigation::{Input, Output, PVTSolutionType},
-    prelude::{Candidate, Config},
+    cfg::RaimOpts,
+    navigation::{Input, Output, PVTSolutionType, StateLayout},
+    prelude::{Config, SV},
+    stats::chi2_quantile,
 };
 
 #[derive(Clone, Debug, PartialEq, Error)]
@@ -27,65 +28,109 @@ pub enum InvalidationCause {
 pub(crate) struct Validator {
     gdop: f64,
     tdop: f64,
-    residuals: DVector<f64>,
+    innovation: Option<f64>,
+    // weighted sum of squared post-fit residuals
+    ssr: f64,
+    // degrees of freedom (redundancy)
+    dof: usize,
+    // normalized post-fit residuals
+    normalized: DVector<f64>,
+    // SV contributing to each residual
+    rows: Vec<Option<SV>>,
 }
 
 impl Validator {
-    pub fn new(
-        apriori_ecef: Vector3<f64>,
-        pool: &[Candidate],
-        input: &Input,
-        output: &Output,
-    ) -> Self {
+    pub fn new(input: &Input, output: &Output) -> Self {
         let gdop = output.gdop;
         let tdop = output.tdop;
-        let mut residuals = DVector::<f64>::zeros(pool.len());
 
-        for (idx, cd) in pool.iter().enumerate() {
-            let sv = input
-                .sv
-                .iter()
-                .filter_map(|(sv, data)| if *sv == cd.sv { Some(data) } else { None })
-                .reduce(|k, _| k)
-                .unwrap();
-
-            let pr = cd.prefered_pseudorange().unwrap().value;
-
-            let x = output.state.estimate();
-
-            let (x, y, z, dt) = (
-                apriori_ecef[0] + x[0],
-                apriori_ecef[1] + x[1],
-                apriori_ecef[2] + x[2],
-                x[3] / SPEED_OF_LIGHT,
-            );
+        // post-fit residuals, linearized at the estimated position
+        let mut x = output.state.estimate();
+        let mut dpos = Vector3::<f64>::zeros();
+        for (i, kind) in StateLayout::POSITION.iter().enumerate() {
+            if let Some(index) = input.layout.index(*kind) {
+                dpos[i] = x[index];
+                x[index] = 0.0;
+            }
+        }
+        let input = input.relinearize(&dpos);
 
-            let sv_pos = cd.state.unwrap().position;
-            let (sv_x, sv_y, sv_z) = (sv_pos[0], sv_pos[1], sv_pos[2]);
+        let residuals = &input.y - &input.g * &x;
+        let ssr = (residuals.transpose() * &input.w * &residuals)[(0, 0)];
 
-            let rho = ((sv_x - x).powi(2) + (sv_y - y).powi(2) + (sv_z - z).powi(2)).sqrt();
+        // states that are not observed (like velocities) do not consume redundancy
+        let observed = (0..input.g.ncols())
+            .filter(|j| input.g.column(*j).iter().any(|g_ij| *g_ij != 0.0))
+            .collect::<Vec<_>>();
+        let g = input.g.select_columns(observed.iter());
+        let dof = g.nrows().saturating_sub(g.ncols());
 
-            let dt = cd.clock_corr.to_seconds() - dt;
+        // residuals cofactor matrix: Qv = W^-1 - G (G'WG)^-1 G'
+        let mut normalized = DVector::<f64>::zeros(residuals.len());
+        let g_prime = g.transpose();
+        if let Some(w_inv) = input.w.clone().try_inverse() {
+            if let Some(n_inv) = (&g_prime * &input.w * &g).try_inverse() {
+                let q_v = w_inv - &g * n_inv * &g_prime;
+                for i in 0..residuals.len() {
+                    if q_v[(i, i)] > 0.0 {
+                        normalized[i] = residuals[i] / q_v[(i, i)].sqrt();
+                    }
+                }
+            }
+        }
 
-            residuals[idx] = pr;
-            residuals[idx] -= rho;
-            residuals[idx] += dt * SPEED_OF_LIGHT;
-            residuals[idx] -= sv.tropo_bias.value().unwrap_or(0.0);
-            residuals[idx] -= sv.iono_bias.value().unwrap_or(0.0);
-            residuals[idx] /= input.w[(idx, idx)];
-            debug!(
-                "{} ({}): coderes={}/w={}",
-                cd.t,
-                cd.sv,
-                residuals[idx],
-                input.w[(idx, idx)]
-            );
+        for (idx, sv) in input.rows.iter().enumerate() {
+            if let Some(sv) = sv {
+                debug!(
+                    "({}): res={:.3E} normalized={:.3E} w={:.3E}",
+                    sv,
+                    residuals[idx],
+                    normalized[idx],
+                    input.w[(idx, idx)]
+                );
+            }
         }
         Self {
-            residuals,
             gdop,
             tdop,
+            innovation: output.innovation,
+            ssr,
+            dof,
+            normalized,
+            rows: input.rows.clone(),
+        }
+    }
+    /*
+     * RAIM global test: chi-square test on weighted post-fit residuals.
+     * Cannot be performed without redundancy.
+     */
+    pub fn raim(&self, opts: &RaimOpts) -> Result<(), InvalidationCause> {
+        if self.dof == 0 {
+            return Ok(());
         }
+        let t = self.ssr / opts.sigma.powi(2);
+        let threshold = chi2_quantile(1.0 - opts.p_fa, self.dof);
+        debug!(
+            "raim: test statistic={:.3E} threshold={:.3E} (dof={})",
+            t, threshold, self.dof
+        );
+        if t > threshold {
+            Err(InvalidationCause::CodeResidual(t))
+        } else {
+            Ok(())
+        }
+    }
+    /*
+     * Returns SV with largest normalized residual: most likely faulty SV
+     */
+    pub fn worst_sv(&self) -> Option<SV> {
+        self.rows
+            .iter()
+            .zip(self.normalized.iter())
+            .filter(|(_, res)| res.is_finite())
+            .filter_map(|(sv, res)| sv.map(|sv| (sv, res.abs())))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(sv, _)| sv)
     }
     /*
      * Solution validation process
@@ -104,6 +149,13 @@ impl Validator {
                 }
             }
         }
+        if let Some(max_innov) = cfg.solver.innovation_threshold {
+            if let Some(innovation) = self.innovation {
+                if innovation > max_innov {
+                    return Err(InvalidationCause::InnovationOutlier(innovation));
+                }
+            }
+        }
         Ok(())
     }
 }
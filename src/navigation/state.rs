@@ -0,0 +1,353 @@
+//! Navigation state vector layout
+use crate::{
+    candidate::Candidate,
+    cfg::{time_system, Config, InterFrequencyBias},
+    prelude::{Constellation, Filter, Method, PVTSolutionType, SV},
+};
+
+/// Physical meaning of one entry of the navigation state vector
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum StateKind {
+    /// ECEF X correction to the apriori position [m]
+    PositionX,
+    /// ECEF Y correction to the apriori position [m]
+    PositionY,
+    /// ECEF Z correction to the apriori position [m]
+    PositionZ,
+    /// ECEF X velocity [m/s]
+    VelocityX,
+    /// ECEF Y velocity [m/s]
+    VelocityY,
+    /// ECEF Z velocity [m/s]
+    VelocityZ,
+    /// Receiver clock offset [m]
+    ClockOffset,
+    /// Receiver clock drift [m/s]
+    ClockDrift,
+    /// Inter System Bias [m]: receiver clock offset to the system time
+    /// of this [Constellation], minus [StateKind::ClockOffset]
+    InterSystemBias(Constellation),
+    /// Glonass code Inter Frequency Bias [m], per frequency channel number
+    InterFrequencyBias,
+    /// Residual Zenith Wet Delay, after modeling [m]
+    TropoZwd,
+    /// Float ionosphere free phase ambiguity [m], per SV and phase tracking arc:
+    /// correction to the a priori ambiguity of this arc
+    Ambiguity(SV, u16),
+}
+
+impl std::fmt::Display for StateKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::PositionX => write!(f, "x"),
+            Self::PositionY => write!(f, "y"),
+            Self::PositionZ => write!(f, "z"),
+            Self::VelocityX => write!(f, "vx"),
+            Self::VelocityY => write!(f, "vy"),
+            Self::VelocityZ => write!(f, "vz"),
+            Self::ClockOffset => write!(f, "dt"),
+            Self::ClockDrift => write!(f, "drift"),
+            Self::InterSystemBias(c) => write!(f, "isb({})", c),
+            Self::InterFrequencyBias => write!(f, "ifb"),
+            Self::TropoZwd => write!(f, "zwd"),
+            Self::Ambiguity(sv, arc) => write!(f, "amb({}:{})", sv, arc),
+        }
+    }
+}
+
+/// [StateLayout] maps each index of the navigation state vector (and
+/// each column of the navigation matrix) to its physical meaning.
+/// The layout grows with the problem we are solving.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateLayout {
+    inner: Vec<StateKind>,
+    reference: Constellation,
+}
+
+impl StateLayout {
+    /// Position correction states, in ECEF order
+    pub const POSITION: [StateKind; 3] = [
+        StateKind::PositionX,
+        StateKind::PositionY,
+        StateKind::PositionZ,
+    ];
+    /// Velocity states, in ECEF order
+    pub const VELOCITY: [StateKind; 3] = [
+        StateKind::VelocityX,
+        StateKind::VelocityY,
+        StateKind::VelocityZ,
+    ];
+    /// Builds the [StateLayout] required to resolve this pool of [Candidate]s
+    pub(crate) fn new(cfg: &Config, pool: &[Candidate]) -> Self {
+        let mut inner = Vec::with_capacity(8);
+        if cfg.sol_type != PVTSolutionType::TimeOnly {
+            inner.extend_from_slice(&Self::POSITION);
+            if cfg.solver.filter == Filter::Kalman && cfg.profile.acceleration_psd().is_some() {
+                // roaming receiver: position-velocity dynamics
+                inner.extend_from_slice(&Self::VELOCITY);
+            }
+        }
+        inner.push(StateKind::ClockOffset);
+        if cfg.solver.filter == Filter::Kalman {
+            // only filters with memory can estimate the drift
+            inner.push(StateKind::ClockDrift);
+        }
+        let (reference, biases) = Self::inter_system_biases(cfg, pool);
+        for constellation in biases {
+            inner.push(StateKind::InterSystemBias(constellation));
+        }
+        if cfg.glonass_ifb == InterFrequencyBias::Estimated {
+            // only observable when several channels contribute
+            let channels = pool
+                .iter()
+                .filter_map(|cd| cd.fdma_channel())
+                .collect::<Vec<_>>();
+            if channels.iter().any(|k| *k != channels[0]) {
+                inner.push(StateKind::InterFrequencyBias);
+            }
+        }
+        if cfg.method == Method::PPP && cfg.modeling.tropo_delay {
+            inner.push(StateKind::TropoZwd);
+        }
+        if cfg.float_ambiguities() {
+            // one state per phase tracking arc
+            for cd in pool.iter() {
+                if let Some(arc) = cd.phase_arc {
+                    inner.push(StateKind::Ambiguity(cd.sv, arc.id));
+                }
+            }
+        }
+        Self { inner, reference }
+    }
+    /*
+     * Reference system time, and system times that need to be estimated:
+     * those that are neither the reference one, nor compensated for.
+     * When no contributing system time is known, the first one becomes the reference.
+     */
+    fn inter_system_biases(
+        cfg: &Config,
+        pool: &[Candidate],
+    ) -> (Constellation, Vec<Constellation>) {
+        let mut systems = pool
+            .iter()
+            .map(|cd| (time_system(cd.sv.constellation), cd.t))
+            .collect::<Vec<_>>();
+        systems.sort_by_key(|(system, _)| *system);
+        systems.dedup_by_key(|(system, _)| *system);
+        let unknown = systems
+            .iter()
+            .filter_map(|(system, t)| {
+                if cfg.system_time_offset(*system, *t).is_none() {
+                    Some(*system)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if !unknown.is_empty() && unknown.len() == systems.len() {
+            (unknown[0], unknown.into_iter().skip(1).collect())
+        } else {
+            (cfg.reference_constellation(), unknown)
+        }
+    }
+    /// Returns the [Constellation] whose system time the receiver clock offset
+    /// ([StateKind::ClockOffset]) refers to. This is the configured one,
+    /// unless it is not contributing and cannot be compensated for.
+    pub fn reference(&self) -> Constellation {
+        self.reference
+    }
+    /// Returns index of the Inter System Bias state of this [Constellation],
+    /// if it is estimated
+    pub fn inter_system_bias(&self, constellation: Constellation) -> Option<usize> {
+        self.index(StateKind::InterSystemBias(time_system(constellation)))
+    }
+    /// Returns number of states
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Returns true if this layout does not describe any state
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Returns index of this [StateKind] in the state vector, if it is estimated
+    pub fn index(&self, kind: StateKind) -> Option<usize> {
+        self.inner.iter().position(|k| *k == kind)
+    }
+    /// Returns [StateKind] at given index in the state vector
+    pub fn kind(&self, index: usize) -> Option<StateKind> {
+        self.inner.get(index).copied()
+    }
+    /// Returns true if position is being estimated
+    pub fn has_position(&self) -> bool {
+        self.index(StateKind::PositionX).is_some()
+    }
+    /// Returns index of the float ambiguity state of this [SV], if it is estimated
+    pub fn ambiguity(&self, sv: SV) -> Option<usize> {
+        self.inner
+            .iter()
+            .position(|k| matches!(k, StateKind::Ambiguity(amb_sv, _) if *amb_sv == sv))
+    }
+    /// Returns true if velocity is being estimated
+    pub fn has_velocity(&self) -> bool {
+        self.index(StateKind::VelocityX).is_some()
+    }
+    /// Iterates over each state, in index order
+    pub fn iter(&self) -> impl Iterator<Item = &StateKind> {
+        self.inner.iter()
+    }
+    /*
+     * Returns a copy of Self, without the states at given indices,
+     * and referred to given system time
+     */
+    pub(crate) fn without(&self, indices: &[usize], reference: Constellation) -> Self {
+        Self {
+            inner: self
+                .inner
+                .iter()
+                .enumerate()
+                .filter_map(|(i, kind)| {
+                    if indices.contains(&i) {
+                        None
+                    } else {
+                        Some(*kind)
+                    }
+                })
+                .collect(),
+            reference,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StateKind, StateLayout};
+    use crate::{
+        cfg::{InterFrequencyBias, InterSystemBias, Profile, SystemTimeOffset},
+        prelude::{
+            Candidate, Carrier, Config, Constellation, Duration, Epoch, Filter, Method,
+            PVTSolutionType, PseudoRange, TimeScale, SV,
+        },
+    };
+    #[test]
+    fn layout() {
+        let mut cfg = Config::static_preset(Method::SPP);
+        let layout = StateLayout::new(&cfg, &[]);
+        assert_eq!(layout.len(), 4);
+        assert!(layout.has_position());
+        assert_eq!(layout.index(StateKind::ClockOffset), Some(3));
+        assert_eq!(layout.index(StateKind::TropoZwd), None);
+
+        cfg.sol_type = PVTSolutionType::TimeOnly;
+        let layout = StateLayout::new(&cfg, &[]);
+        assert_eq!(layout.len(), 1);
+        assert!(!layout.has_position());
+        assert_eq!(layout.kind(0), Some(StateKind::ClockOffset));
+
+        let mut cfg = Config::static_preset(Method::PPP);
+        let layout = StateLayout::new(&cfg, &[]);
+        assert_eq!(layout.index(StateKind::TropoZwd), Some(4));
+
+        cfg.solver.filter = Filter::Kalman;
+        let layout = StateLayout::new(&cfg, &[]);
+        assert_eq!(layout.index(StateKind::ClockDrift), Some(4));
+        assert_eq!(layout.index(StateKind::TropoZwd), Some(5));
+        assert!(!layout.has_velocity());
+
+        cfg.profile = Profile::Pedestrian;
+        let layout = StateLayout::new(&cfg, &[]);
+        assert!(layout.has_velocity());
+        assert_eq!(layout.index(StateKind::VelocityX), Some(3));
+        assert_eq!(layout.index(StateKind::ClockOffset), Some(6));
+    }
+    #[test]
+    fn inter_system_biases() {
+        let pool = [
+            Constellation::GPS,
+            Constellation::Galileo,
+            Constellation::EGNOS,
+            Constellation::Galileo,
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, constellation)| {
+            Candidate::new(
+                SV::new(*constellation, i as u8 + 1),
+                Epoch::default(),
+                Duration::default(),
+                None,
+                vec![],
+                vec![],
+            )
+        })
+        .collect::<Vec<_>>();
+
+        let mut cfg = Config::static_preset(Method::SPP);
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.len(), 5);
+        assert_eq!(layout.inter_system_bias(Constellation::Galileo), Some(4));
+        assert_eq!(layout.inter_system_bias(Constellation::GPS), None);
+        assert_eq!(layout.inter_system_bias(Constellation::EGNOS), None);
+        assert_eq!(layout.reference(), Constellation::GPS);
+
+        // galileo is now the reference
+        cfg.timescale = TimeScale::GST;
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.inter_system_bias(Constellation::GPS), Some(4));
+        assert_eq!(layout.inter_system_bias(Constellation::Galileo), None);
+        assert_eq!(layout.reference(), Constellation::Galileo);
+
+        // reference is not contributing: another system takes its place
+        let layout = StateLayout::new(&cfg, &pool[2..3]);
+        assert_eq!(layout.len(), 4);
+        assert_eq!(layout.reference(), Constellation::GPS);
+
+        // broadcast offsets
+        cfg.timescale = TimeScale::GPST;
+        cfg.inter_system_bias = InterSystemBias::Broadcast(vec![SystemTimeOffset {
+            constellation: Constellation::Galileo,
+            t_ref: Epoch::default(),
+            a0: 1.0E-9,
+            a1: 0.0,
+        }]);
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.len(), 4);
+        assert_eq!(layout.reference(), Constellation::GPS);
+        assert_eq!(
+            cfg.system_time_offset(Constellation::Galileo, Epoch::default()),
+            Some(1.0E-9)
+        );
+    }
+    #[test]
+    fn glonass_ifb() {
+        let pool = [(1, 0), (2, -7), (3, 0), (4, 4)]
+            .iter()
+            .map(|(prn, k)| {
+                Candidate::new(
+                    SV::new(Constellation::Glonass, *prn),
+                    Epoch::default(),
+                    Duration::default(),
+                    None,
+                    vec![PseudoRange {
+                        carrier: Carrier::G1(*k),
+                        value: 20.0E6,
+                        snr: None,
+                        code: None,
+                    }],
+                    vec![],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut cfg = Config::static_preset(Method::SPP);
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.index(StateKind::InterFrequencyBias), Some(4));
+
+        // single channel: not observable
+        let layout = StateLayout::new(&cfg, &[pool[0].clone(), pool[2].clone()]);
+        assert_eq!(layout.index(StateKind::InterFrequencyBias), None);
+
+        cfg.glonass_ifb = InterFrequencyBias::Known(vec![]);
+        let layout = StateLayout::new(&cfg, &pool);
+        assert_eq!(layout.index(StateKind::InterFrequencyBias), None);
+    }
+}
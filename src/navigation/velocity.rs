@@ -0,0 +1,134 @@
+//! Instantaneous velocity, from range rate (Doppler) observations
+use nalgebra::{DMatrix, DVector, Matrix3, Vector3};
+
+use crate::candidate::Candidate;
+
+/// Instantaneous velocity estimate
+#[derive(Debug, Clone)]
+pub(crate) struct VelocityOutput {
+    /// Receiver velocity [m/s] ECEF (null when not estimated)
+    pub velocity: Vector3<f64>,
+    /// Receiver clock drift [m/s]
+    pub clock_drift: f64,
+    /// Receiver clock drift variance [m²/s²]
+    pub drift_variance: f64,
+    /// Velocity covariance [m²/s²] ECEF
+    pub q: Matrix3<f64>,
+}
+
+/*
+ * Resolves receiver velocity (when `has_position`) and clock drift
+ * from the range rate of each candidate with both Doppler observation and SV velocity.
+ * rx: receiver position [m ECEF].
+ * Returns None when not enough range rates are available.
+ */
+pub(crate) fn resolve(
+    rx: &Vector3<f64>,
+    pool: &[Candidate],
+    has_position: bool,
+) -> Option<VelocityOutput> {
+    let nstates = if has_position { 4 } else { 1 };
+
+    // (line of sight, range rate, sv velocity)
+    let rates = pool
+        .iter()
+        .filter_map(|cd| {
+            let range_rate = cd.range_rate()?;
+            let state = cd.state?;
+            let sv_velocity = state.velocity()?;
+            let los = (state.position - rx).normalize();
+            Some((los, range_rate, sv_velocity))
+        })
+        .collect::<Vec<_>>();
+
+    let nrows = rates.len();
+    if nrows < nstates {
+        return None;
+    }
+
+    // range_rate = los.(v_sv - v_rx) + drift
+    let mut g = DMatrix::<f64>::zeros(nrows, nstates);
+    let mut y = DVector::<f64>::zeros(nrows);
+    for (i, (los, range_rate, sv_velocity)) in rates.iter().enumerate() {
+        if has_position {
+            for j in 0..3 {
+                g[(i, j)] = -los[j];
+            }
+        }
+        g[(i, nstates - 1)] = 1.0_f64;
+        y[i] = range_rate - los.dot(sv_velocity);
+    }
+
+    let g_prime = g.transpose();
+    let q = (&g_prime * &g).try_inverse()?;
+    let x = &q * (&g_prime * &y);
+
+    // a posteriori variance factor, when redundancy allows it
+    let dof = nrows - nstates;
+    let sigma2 = if dof > 0 {
+        (&y - &g * &x).norm_squared() / dof as f64
+    } else {
+        1.0_f64
+    };
+
+    let mut output = VelocityOutput {
+        velocity: Vector3::<f64>::zeros(),
+        clock_drift: x[nstates - 1],
+        drift_variance: q[(nstates - 1, nstates - 1)] * sigma2,
+        q: Matrix3::<f64>::zeros(),
+    };
+    if has_position {
+        output.velocity = Vector3::new(x[0], x[1], x[2]);
+        output.q = q.fixed_view::<3, 3>(0, 0) * sigma2;
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve;
+    use crate::{
+        prelude::{Candidate, Carrier, Doppler, Duration, Epoch, InterpolationResult, Vector3},
+        tests::geometry,
+    };
+    #[test]
+    fn doppler_velocity() {
+        let rx = geometry::rx();
+        let rx_velocity = Vector3::new(1.5, -2.0, 0.5);
+        let drift = 12.0;
+        let pool = geometry::vehicles(5)
+            .iter()
+            .enumerate()
+            .map(|(i, (sv, position))| {
+                let sv_velocity = Vector3::new(-1500.0, 2000.0 - 500.0 * i as f64, 800.0);
+                let los = (position - rx).normalize();
+                let range_rate = los.dot(&(sv_velocity - rx_velocity)) + drift;
+                let mut cd = Candidate::new(
+                    *sv,
+                    Epoch::default(),
+                    Duration::default(),
+                    None,
+                    vec![],
+                    vec![],
+                )
+                .with_doppler(vec![Doppler {
+                    carrier: Carrier::L1,
+                    value: -range_rate / Carrier::L1.wavelength(),
+                    snr: None,
+                }]);
+                cd.set_state(
+                    InterpolationResult::from_position((position[0], position[1], position[2]))
+                        .with_velocity(sv_velocity),
+                );
+                cd
+            })
+            .collect::<Vec<_>>();
+
+        let output = resolve(&rx, &pool, true).unwrap();
+        assert!((output.velocity - rx_velocity).norm() < 1.0E-6);
+        assert!((output.clock_drift - drift).abs() < 1.0E-6);
+
+        let output = resolve(&rx, &pool[..3], true);
+        assert!(output.is_none(), "not enough range rates");
+    }
+}
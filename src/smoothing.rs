@@ -0,0 +1,200 @@
+//! Carrier smoothing of Pseudo Range observations (Hatch filter)
+use crate::{
+    candidate::{Candidate, PhaseRange},
+    cycle_slip::CycleSlip,
+    prelude::{Carrier, SV},
+};
+use log::debug;
+use std::collections::HashMap;
+
+/// Smoothing state of one signal
+struct SignalTracker {
+    /// Number of averaged samples, up to the window length
+    n: usize,
+    /// Smoothed pseudo range [m]
+    smoothed: f64,
+    /// Previous phase combination [m]
+    phase: f64,
+    /// True when the divergence free phase combination is used
+    divergence_free: bool,
+}
+
+/*
+ * Phase combination used to smooth the pseudo range on this signal.
+ * When a phase observation on a second frequency is available, its
+ * ionospheric delay matches the code delay (divergence free).
+ * Otherwise, the single frequency phase is used (Hatch filter):
+ * the smoothed code then diverges, at twice the ionospheric delay rate.
+ */
+fn phase_combination(phase: &PhaseRange, other: Option<&PhaseRange>) -> (f64, bool) {
+    match other {
+        Some(other) => {
+            let gamma = (phase.carrier.frequency() / other.carrier.frequency()).powi(2);
+            let value = phase.value + 2.0 / (gamma - 1.0) * (phase.value - other.value);
+            (value, true)
+        },
+        None => (phase.value, false),
+    }
+}
+
+/// [CodeSmoother] smoothes Pseudo Range observations with Phase Range
+/// observations on the same signal, per SV and per signal.
+pub(crate) struct CodeSmoother {
+    /// Window length, in number of samples
+    window: usize,
+    /// Signal trackers
+    trackers: HashMap<(SV, Carrier), SignalTracker>,
+}
+
+impl CodeSmoother {
+    /// Builds new [CodeSmoother] with given window length.
+    pub fn new(window: usize) -> Self {
+        Self {
+            window: window.max(1),
+            trackers: HashMap::with_capacity(16),
+        }
+    }
+    /*
+     * Smoothes Pseudo Range observations of this candidate, in place.
+     * Smoothing restarts on cycle slips and when phase tracking is interrupted.
+     */
+    pub fn smooth(&mut self, cd: &mut Candidate, slips: &[CycleSlip]) {
+        if slips.iter().any(|slip| slip.sv == cd.sv) {
+            self.trackers.retain(|(sv, _), _| *sv != cd.sv);
+        }
+        let (l_1, l_j) = (cd.l1_phaserange().cloned(), cd.lj_phaserange().cloned());
+        for pr in cd.pseudo_range.iter_mut() {
+            let key = (cd.sv, pr.carrier);
+            let phase = match cd.phase_range.iter().find(|ph| ph.carrier == pr.carrier) {
+                Some(phase) => phase,
+                None => {
+                    self.trackers.remove(&key);
+                    continue;
+                },
+            };
+            let other = [&l_1, &l_j]
+                .into_iter()
+                .flatten()
+                .find(|ph| ph.carrier.frequency() != pr.carrier.frequency());
+            let (phase, divergence_free) = phase_combination(phase, other);
+
+            match self.trackers.get_mut(&key) {
+                Some(tracker) if tracker.divergence_free == divergence_free => {
+                    tracker.n = (tracker.n + 1).min(self.window);
+                    let n = tracker.n as f64;
+                    tracker.smoothed =
+                        pr.value / n + (n - 1.0) / n * (tracker.smoothed + phase - tracker.phase);
+                    tracker.phase = phase;
+                    debug!(
+                        "{}({}/{}): smoothed code {:.3} -> {:.3}",
+                        cd.t, cd.sv, pr.carrier, pr.value, tracker.smoothed
+                    );
+                    pr.value = tracker.smoothed;
+                },
+                _ => {
+                    self.trackers.insert(
+                        key,
+                        SignalTracker {
+                            n: 1,
+                            smoothed: pr.value,
+                            phase,
+                            divergence_free,
+                        },
+                    );
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CodeSmoother;
+    use crate::prelude::{
+        Candidate, Carrier, Constellation, CycleSlip, Duration, Epoch, PhaseRange, PseudoRange,
+        SlipCause, SV,
+    };
+
+    fn candidate(i: usize, noise: f64) -> Candidate {
+        let range = 2.2E7 + 500.0 * i as f64;
+        // ionospheric delay, increasing over time
+        let iono = 5.0 + 0.01 * i as f64;
+        let (f_1, f_2) = (Carrier::L1.frequency(), Carrier::L2.frequency());
+        let iono_2 = iono * (f_1 / f_2).powi(2);
+        Candidate::new(
+            SV::new(Constellation::GPS, 1),
+            Epoch::default() + Duration::from_seconds(i as f64),
+            Duration::default(),
+            None,
+            vec![
+                PseudoRange {
+                    carrier: Carrier::L1,
+                    value: range + iono + noise,
+                    snr: None,
+                    code: None,
+                },
+                PseudoRange {
+                    carrier: Carrier::L2,
+                    value: range + iono_2 - noise,
+                    snr: None,
+                    code: None,
+                },
+            ],
+            vec![
+                PhaseRange {
+                    carrier: Carrier::L1,
+                    value: range - iono + 10.0,
+                    ambiguity: None,
+                    snr: None,
+                    code: None,
+                    lli: None,
+                    lock_time: None,
+                },
+                PhaseRange {
+                    carrier: Carrier::L2,
+                    value: range - iono_2 - 20.0,
+                    ambiguity: None,
+                    snr: None,
+                    code: None,
+                    lli: None,
+                    lock_time: None,
+                },
+            ],
+        )
+    }
+
+    #[test]
+    fn divergence_free_smoothing() {
+        let mut smoother = CodeSmoother::new(100);
+        for i in 0..200 {
+            let noise = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let mut cd = candidate(i, noise);
+            let slips = if i == 150 {
+                vec![CycleSlip {
+                    t: cd.t,
+                    sv: cd.sv,
+                    cause: SlipCause::LossOfLock,
+                }]
+            } else {
+                vec![]
+            };
+            smoother.smooth(&mut cd, &slips);
+            let raw = candidate(i, 0.0);
+            for (pr, truth) in cd.pseudo_range.iter().zip(raw.pseudo_range.iter()) {
+                let err = (pr.value - truth.value).abs();
+                if i == 150 {
+                    // reset
+                    assert!((err - 1.0).abs() < 1.0E-6, "reset error {}", err);
+                } else if (i > 100 && i < 150) || i > 190 {
+                    assert!(
+                        err < 0.1,
+                        "{} smoothing error {} at #{}",
+                        pr.carrier,
+                        err,
+                        i
+                    );
+                }
+            }
+        }
+    }
+}
@@ -15,21 +15,30 @@ use nyx::{
         eclipse::{eclipse_state, EclipseState},
         Orbit, SPEED_OF_LIGHT,
     },
-    md::prelude::{Arc, Cosm, Frame},
+    md::prelude::{Arc, Bodies, Cosm, Frame, LightTimeCalc},
 };
 
 use crate::{
-    ambiguity::AmbiguitySolver,
+    ambiguity::{fix_ambiguities, AmbiguitySolver},
+    antex::Antex,
+    attitude::AttitudeTracker,
     bancroft::Bancroft,
     bias::{IonosphereBias, TroposphereBias},
     candidate::Candidate,
-    cfg::{Config, Method},
+    cfg::{time_system, Config, Method},
+    cycle_slip::{CycleSlipDetector, SlipCause},
     navigation::{
-        solutions::validator::{InvalidationCause, Validator as SolutionValidator},
-        Input as NavigationInput, InstrumentBias, Navigation, PVTSolution, PVTSolutionType,
+        solutions::{
+            integrity::protection_levels,
+            validator::{InvalidationCause, Validator as SolutionValidator},
+        },
+        velocity, FixedSolution, Input as NavigationInput, InstrumentBias, Navigation, PVTSolution,
+        PVTSolutionType, StateKind, StateLayout,
     },
     position::Position,
-    prelude::{Duration, Epoch, SV},
+    prelude::{Constellation, Duration, Epoch, SV},
+    smoothing::CodeSmoother,
+    tides::solid_earth_tide,
 };
 
 #[derive(Debug, Clone, PartialEq, Error)]
@@ -80,10 +89,13 @@ pub struct InterpolationResult {
     pub elevation: f64,
     /// Azimuth compared to reference position and magnetic North in [°]
     pub azimuth: f64,
-    /// APC Position vector in [m] ECEF
+    /// Position vector in [m] ECEF: Antenna Phase Center (APC),
+    /// or Center of Mass (CoM) when built with [Self::from_center_of_mass]
     pub position: Vector3<f64>,
     // Velocity vector in [m/s] ECEF that we calculated ourselves
     velocity: Option<Vector3<f64>>,
+    // True when position refers to the Center of Mass
+    center_of_mass: bool,
 }
 
 impl InterpolationResult {
@@ -95,8 +107,32 @@ impl InterpolationResult {
             azimuth: 0.0_f64,
             elevation: 0.0_f64,
             position: Vector3::<f64>::new(position.0, position.1, position.2),
+            center_of_mass: false,
         }
     }
+    /// Builds InterpolationResults from Center of Mass (CoM) position coordinates,
+    /// expressed in ECEF [m], as most precise orbit products are.
+    /// The satellite antenna phase center offsets and variations are then compensated for,
+    /// when an ANTEX description of this vehicle is provided (see [Solver::with_antex]).
+    pub fn from_center_of_mass(position: (f64, f64, f64)) -> Self {
+        let mut s = Self::from_position(position);
+        s.center_of_mass = true;
+        s
+    }
+    /// Returns true when position coordinates refer to the Center of Mass (CoM),
+    /// false when they refer to the Antenna Phase Center (APC).
+    pub fn is_center_of_mass(&self) -> bool {
+        self.center_of_mass
+    }
+    /// Augment self with the SV velocity vector, expressed in [m/s] ECEF.
+    /// When not provided, we derive it from successive positions ourselves.
+    /// Instantaneous receiver velocity (from Doppler observations) is more
+    /// accurate when you provide the SV velocity (from the ephemeris) yourself.
+    pub fn with_velocity(&self, velocity: Vector3<f64>) -> Self {
+        let mut s = *self;
+        s.velocity = Some(velocity);
+        s
+    }
     /// Augment self to fully defined with both Elevation and Azimuth angles
     pub(crate) fn with_elevation_azimuth(&self, position: (f64, f64, f64)) -> Self {
         let (x, y, z) = position;
@@ -136,6 +172,7 @@ impl InterpolationResult {
             elevation,
             position: self.position,
             velocity: self.velocity,
+            center_of_mass: self.center_of_mass,
         }
     }
     pub(crate) fn velocity(&self) -> Option<Vector3<f64>> {
@@ -181,8 +218,14 @@ where
     earth_frame: Frame,
     // Sun / Star body frame
     sun_frame: Frame,
+    // Earth body fixed frame
+    earth_fixed_frame: Frame,
     // Navigator
     nav: Navigation,
+    // Cycle slip detector
+    cycle_slips: CycleSlipDetector,
+    // Pseudo range smoothing
+    smoother: CodeSmoother,
     // Solver
     ambiguity: AmbiguitySolver,
     // Post fit KF
@@ -196,6 +239,12 @@ where
     prev_used: Vec<SV>,
     /// Stored previous SV state
     prev_sv_state: HashMap<SV, (Epoch, Vector3<f64>)>,
+    /// Accumulated phase wind-up [cycles]
+    prev_windup: HashMap<SV, f64>,
+    /// Yaw attitude models
+    attitudes: AttitudeTracker,
+    /// Antenna models
+    antex: Option<Antex>,
 }
 
 impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I> {
@@ -211,6 +260,7 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
         let cosmic = Cosm::de438();
         let sun_frame = cosmic.frame("Sun J2000");
         let earth_frame = cosmic.frame("EME2000");
+        let earth_fixed_frame = cosmic.frame("IAU Earth");
 
         /*
          * print more infos
@@ -225,6 +275,7 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             cosmic,
             sun_frame,
             earth_frame,
+            earth_fixed_frame,
             initial: {
                 if let Some(ref initial) = initial {
                     let geo = initial.geodetic();
@@ -238,13 +289,32 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             prev_used: vec![],
             cfg: cfg.clone(),
             prev_solution: None,
-            // TODO
-            ambiguity: AmbiguitySolver::new(Duration::from_seconds(120.0)),
+            cycle_slips: CycleSlipDetector::new(&cfg.ambiguity),
+            smoother: CodeSmoother::new(cfg.smoothing_window),
+            ambiguity: AmbiguitySolver::new(&cfg.ambiguity),
             // postfit_kf: None,
             prev_sv_state: HashMap::new(),
-            nav: Navigation::new(cfg.solver.filter),
+            prev_windup: HashMap::new(),
+            attitudes: AttitudeTracker::default(),
+            antex: None,
+            nav: Navigation::new(cfg),
         })
     }
+    /// Provides the antenna models ([Antex]) to the [Solver].
+    /// Satellite antenna phase center offsets and variations are then compensated for,
+    /// when the interpolated positions refer to the Center of Mass
+    /// (see [InterpolationResult::from_center_of_mass]).
+    /// The receiver antenna phase center offsets and variations are compensated for as well,
+    /// when the antenna described in the [Config] is found.
+    pub fn with_antex(mut self, antex: Antex) -> Self {
+        if let Some(name) = &self.cfg.rx_antenna {
+            if antex.rx_antenna(name).is_none() {
+                warn!("receiver antenna \"{}\" is not described", name);
+            }
+        }
+        self.antex = Some(antex);
+        self
+    }
     /// [PVTSolution] resolution attempt.
     /// ## Inputs
     /// - t: desired [Epoch]
@@ -269,6 +339,24 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
         let solver_opts = &self.cfg.solver;
         let interp_order = self.cfg.interp_order;
 
+        /* validate observables, apply signal selection policies */
+        let pool: Vec<Candidate> = pool
+            .iter()
+            .map(|cd| {
+                let mut cd = cd.clone();
+                cd.validate_codes();
+                if let Some(policy) = self
+                    .cfg
+                    .signal_priority
+                    .iter()
+                    .find(|policy| policy.constellation == cd.sv.constellation)
+                {
+                    cd.apply_signal_priority(&policy.codes);
+                }
+                cd
+            })
+            .collect();
+
         /* apply signal quality and condition filters */
         let pool: Vec<Candidate> = pool
             .iter()
@@ -392,16 +480,33 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
                 .insert(cd.sv, (cd.t_tx, cd.state.unwrap().position));
         }
 
+        /* eclipse states: eclipse filter and attitude models */
+        let windup_modeling = method == Method::PPP && modeling.phase_windup;
+        let sv_antenna_modeling = self.antex.is_some()
+            && pool
+                .iter()
+                .any(|cd| cd.state.map(|s| s.is_center_of_mass()).unwrap_or(false));
+        let attitude_modeling = windup_modeling || sv_antenna_modeling;
+        if self.cfg.min_sv_sunlight_rate.is_some() || attitude_modeling {
+            for cd in pool.iter_mut() {
+                let state = cd.state.unwrap(); // infaillible
+                let orbit = state.orbit(cd.t, self.earth_frame);
+                cd.eclipse = Some(eclipse_state(
+                    &orbit,
+                    self.sun_frame,
+                    self.earth_frame,
+                    &self.cosmic,
+                ));
+            }
+        }
+
         /* apply eclipse filter (if need be) */
         if let Some(min_rate) = self.cfg.min_sv_sunlight_rate {
             pool.retain(|cd| {
-                let state = cd.state.unwrap(); // infaillible
-                let orbit = state.orbit(cd.t, self.earth_frame);
-                let state = eclipse_state(&orbit, self.sun_frame, self.earth_frame, &self.cosmic);
-                let eclipsed = match state {
-                    EclipseState::Umbra => true,
-                    EclipseState::Visibilis => false,
-                    EclipseState::Penumbra(r) => r < min_rate,
+                let eclipsed = match cd.eclipse {
+                    Some(EclipseState::Umbra) => true,
+                    Some(EclipseState::Penumbra(r)) => r < min_rate,
+                    _ => false,
                 };
                 if eclipsed {
                     debug!("{} ({}): eclipsed", cd.t, cd.sv);
@@ -436,15 +541,27 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             )));
         }
 
+        // Detect cycle slips
+        let cycle_slips = self.cycle_slips.detect(&pool);
+
+        // Pseudo range smoothing
+        if self.cfg.code_smoothing && method != Method::PPP {
+            for cd in pool.iter_mut() {
+                self.smoother.smooth(cd, &cycle_slips);
+            }
+        }
+
         // Resolve ambiguities
-        let ambiguities = if method == Method::PPP {
-            self.ambiguity.resolve(&pool)
+        let mut ambiguities = if method == Method::PPP {
+            let ambiguities = self.ambiguity.resolve(&pool, &cycle_slips);
+            for cd in pool.iter_mut() {
+                cd.phase_arc = self.ambiguity.phase_arc(cd.sv);
+            }
+            ambiguities
         } else {
             Default::default()
         };
 
-        // Prepare for NAV:
-        Self::retain_best_elevation(&mut pool, min_required);
         // Sort by PRN to form consistant matrix
         pool.sort_by(|cd_a, cd_b| cd_a.sv.prn.partial_cmp(&cd_b.sv.prn).unwrap());
 
@@ -457,21 +574,72 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
         );
         let (lat_ddeg, lon_ddeg) = (deg2rad(lat_rad), deg2rad(lon_rad));
 
-        let mut w = self.cfg.solver.weight_matrix(); //sv.values().map(|sv| sv.elevation).collect());
-                                                     // // Reduce contribution of newer (rising) vehicles (rising)
-                                                     // for (i, cd) in pool.iter().enumerate() {
-                                                     //     if !self.prev_used.contains(&cd.sv) {
-                                                     //         w[(i, i)] = 0.05;
-                                                     //         w[(2 * i, 2 * i)] = 0.05;
-                                                     //     }
-                                                     // }
+        // Attitude, satellite antenna and phase wind-up (accumulated per SV)
+        if attitude_modeling {
+            let r_sun = self.body_position(Bodies::Sun, t);
+            let r_rx = Vector3::new(x0, y0, z0);
+            for slip in cycle_slips.iter() {
+                if slip.cause == SlipCause::DataGap {
+                    self.prev_windup.remove(&slip.sv);
+                }
+            }
+            for cd in pool.iter_mut() {
+                let state = cd.state.unwrap(); // infaillible
+                let eclipsed = !matches!(cd.eclipse, Some(EclipseState::Visibilis) | None);
+                cd.attitude = self.attitudes.attitude(
+                    cd.sv,
+                    cd.t,
+                    state.position,
+                    state.velocity(),
+                    r_sun,
+                    eclipsed,
+                );
+                if state.is_center_of_mass() {
+                    let antenna = self
+                        .antex
+                        .as_ref()
+                        .and_then(|antex| antex.sv_antenna(cd.sv, cd.t));
+                    match antenna {
+                        Some(antenna) => cd.sv_antenna_model(antenna, r_rx),
+                        None => warn!("{} ({}): unknown satellite antenna", cd.t, cd.sv),
+                    }
+                }
+                if windup_modeling {
+                    cd.wind_up = self.prev_windup.get(&cd.sv).copied().unwrap_or(0.0);
+                    let windup = cd.windup_correction(r_rx, lat_rad, lon_rad);
+                    debug!("{} ({}): phase windup {:.3} cycles", cd.t, cd.sv, windup);
+                    self.prev_windup.insert(cd.sv, windup);
+                }
+            }
+        }
+
+        // Receiver antenna phase center
+        let rx_antenna = match (&self.antex, &self.cfg.rx_antenna) {
+            (Some(antex), Some(name)) => antex.rx_antenna(name),
+            _ => None,
+        };
+        if let Some(antenna) = rx_antenna {
+            for cd in pool.iter_mut() {
+                cd.rx_antenna_model(antenna, lat_rad, lon_rad);
+            }
+        }
+
+        // Solid Earth tides: displacement of the a-priori station position
+        let apriori = if modeling.solid_tides {
+            let r_sun = self.body_position(Bodies::Sun, t);
+            let r_moon = self.body_position(Bodies::Luna, t);
+            let tides = solid_earth_tide(t, lat_rad, lon_rad, r_sun, r_moon);
+            debug!("{}: solid tides displacement {}", t, tides);
+            (x0 + tides[0], y0 + tides[1], z0 + tides[2])
+        } else {
+            (x0, y0, z0)
+        };
 
         let input = match NavigationInput::new(
-            (x0, y0, z0),
+            apriori,
             (lat_ddeg, lon_ddeg, altitude_above_sea_m),
             &self.cfg,
             &pool,
-            w,
             &ambiguities,
             iono_bias,
             tropo_bias,
@@ -483,25 +651,34 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             },
         };
 
-        self.prev_used = pool.iter().map(|cd| cd.sv).collect::<Vec<_>>();
+        // Regular Iteration, with possible fault detection and exclusion
+        let (input, output, excluded) =
+            match self
+                .nav
+                .resolve_fde(t, input, self.cfg.solver.raim.as_ref(), min_required)
+            {
+                Ok(resolved) => resolved,
+                Err(Error::InvalidatedSolution(cause)) => {
+                    return Err(Error::InvalidatedSolution(cause));
+                },
+                Err(e) => {
+                    error!("Failed to resolve: {}", e);
+                    return Err(Error::NavigationError);
+                },
+            };
+        pool.retain(|cd| !excluded.contains(&cd.sv));
 
-        // Regular Iteration
-        let output = match self.nav.resolve(&input) {
-            Ok(output) => output,
-            Err(e) => {
-                error!("Failed to resolve: {}", e);
-                return Err(Error::NavigationError);
-            },
-        };
+        self.prev_used = pool.iter().map(|cd| cd.sv).collect::<Vec<_>>();
 
-        let x = output.state.estimate();
-        debug!("x: {}", x);
+        let state = &output.state;
+        debug!("x: {}", state.estimate());
 
-        let position = match method {
-            // Method::PPP => Vector3::new(x[4] + x0, x[5] + y0, x[6] + z0),
-            Method::PPP => Vector3::new(x[0] + x0, x[1] + y0, x[2] + z0),
-            Method::SPP | Method::CPP => Vector3::new(x[0] + x0, x[1] + y0, x[2] + z0),
-        };
+        let position = Vector3::new(
+            x0 + state.value(StateKind::PositionX).unwrap_or(0.0),
+            y0 + state.value(StateKind::PositionY).unwrap_or(0.0),
+            z0 + state.value(StateKind::PositionZ).unwrap_or(0.0),
+        );
+        let clock_offset = state.value(StateKind::ClockOffset).unwrap_or(0.0);
 
         // Bias
         let mut bias = InstrumentBias::new();
@@ -518,19 +695,140 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
         //    }
         //}
 
+        // Protection levels
+        let (hpl, vpl) = match &self.cfg.solver.integrity {
+            Some(opts) => protection_levels(&input, lat_rad, lon_rad, opts),
+            None => (None, None),
+        };
+
+        // Instantaneous velocity, from Doppler observations
+        let inst_velocity = velocity::resolve(&position, &pool, input.layout.has_position());
+        if let Some(inst_velocity) = &inst_velocity {
+            debug!(
+                "velocity: {} clock drift: {:.3E} m/s",
+                inst_velocity.velocity, inst_velocity.clock_drift
+            );
+        }
+
+        // Velocity: from Doppler observations, otherwise from the filter dynamics
+        let (velocity, velocity_covar) = match &inst_velocity {
+            Some(inst_velocity) => (inst_velocity.velocity, Some(inst_velocity.q)),
+            None => match state.covariance(&StateLayout::VELOCITY) {
+                Some(covar) => (
+                    Vector3::new(
+                        state.value(StateKind::VelocityX).unwrap_or(0.0),
+                        state.value(StateKind::VelocityY).unwrap_or(0.0),
+                        state.value(StateKind::VelocityZ).unwrap_or(0.0),
+                    ),
+                    Some(covar.fixed_view::<3, 3>(0, 0).into()),
+                ),
+                None => (Vector3::<f64>::default(), None),
+            },
+        };
+
+        // Clock drift: from Doppler observations, otherwise from the filter
+        let (clock_drift, clock_drift_sigma) = match &inst_velocity {
+            Some(inst_velocity) => (
+                Some(inst_velocity.clock_drift / SPEED_OF_LIGHT),
+                Some(inst_velocity.drift_variance.sqrt() / SPEED_OF_LIGHT),
+            ),
+            None => (
+                state
+                    .value(StateKind::ClockDrift)
+                    .map(|drift| drift / SPEED_OF_LIGHT),
+                state
+                    .variance(StateKind::ClockDrift)
+                    .map(|var| var.sqrt() / SPEED_OF_LIGHT),
+            ),
+        };
+
+        // Inter System Biases: estimated, otherwise broadcast
+        let mut isb = HashMap::<Constellation, Duration>::new();
+        for cd in pool.iter().filter(|cd| input.sv.contains_key(&cd.sv)) {
+            let system = time_system(cd.sv.constellation);
+            if system == state.layout().reference() || isb.contains_key(&system) {
+                continue;
+            }
+            let bias = match state.value(StateKind::InterSystemBias(system)) {
+                Some(bias) => Some(bias / SPEED_OF_LIGHT),
+                None => self
+                    .cfg
+                    .system_time_offset(system, cd.t)
+                    .map(|offset| -offset),
+            };
+            if let Some(bias) = bias {
+                isb.insert(system, Duration::from_seconds(bias));
+            }
+        }
+
+        // Integer ambiguity resolution
+        let fixed = if self.cfg.float_ambiguities() {
+            fix_ambiguities(&pool, state, &self.cfg.ambiguity, &mut ambiguities).and_then(|fix| {
+                let x = state.conditioned(&fix.kinds, &fix.residuals)?;
+                let value = |kind: StateKind| {
+                    state
+                        .layout()
+                        .index(kind)
+                        .map(|index| x[index])
+                        .unwrap_or(0.0)
+                };
+                Some(FixedSolution {
+                    position: Vector3::new(
+                        x0 + value(StateKind::PositionX),
+                        y0 + value(StateKind::PositionY),
+                        z0 + value(StateKind::PositionZ),
+                    ),
+                    dt: Duration::from_seconds(value(StateKind::ClockOffset) / SPEED_OF_LIGHT),
+                    sv: fix.sv,
+                    ratio: fix.ratio,
+                    success_rate: fix.success_rate,
+                })
+            })
+        } else {
+            None
+        };
+
+        // Float ambiguities
+        let float_ambiguities = pool
+            .iter()
+            .filter_map(|cd| {
+                let arc = cd.phase_arc?;
+                let correction = state.value(StateKind::Ambiguity(cd.sv, arc.id))?;
+                Some((cd.sv, arc.apriori + correction))
+            })
+            .collect::<HashMap<_, _>>();
+
         // Form Solution
         let mut solution = PVTSolution {
             // bias,
             position,
             ambiguities,
+            float_ambiguities,
+            fixed,
+            cycle_slips,
             gdop: output.gdop,
             tdop: output.tdop,
             pdop: output.pdop,
             sv: input.sv.clone(),
+            excluded,
+            hpl,
+            vpl,
             q: output.q_covar4x4(),
-            timescale: self.cfg.timescale,
-            velocity: Vector3::<f64>::default(),
-            dt: Duration::from_seconds(x[3] / SPEED_OF_LIGHT),
+            timescale: state
+                .layout()
+                .reference()
+                .timescale()
+                .unwrap_or(self.cfg.timescale),
+            reference: state.layout().reference(),
+            velocity,
+            velocity_covar,
+            dt: Duration::from_seconds(clock_offset / SPEED_OF_LIGHT),
+            clock_drift,
+            clock_drift_sigma,
+            isb,
+            glonass_ifb: state.value(StateKind::InterFrequencyBias),
+            iterations: output.iterations,
+            correction: output.correction,
         };
 
         // First solution
@@ -541,8 +839,7 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             return Err(Error::InvalidatedSolution(InvalidationCause::FirstSolution));
         }
 
-        let validator =
-            SolutionValidator::new(Vector3::<f64>::new(x0, y0, z0), &pool, &input, &output);
+        let validator = SolutionValidator::new(&input, &output);
 
         match validator.validate(&self.cfg) {
             Ok(_) => {
@@ -573,9 +870,12 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             //}
         }
 
-        if let Some((prev_t, prev_solution)) = &self.prev_solution {
-            solution.velocity =
-                (solution.position - prev_solution.position) / (t - *prev_t).to_seconds();
+        // Velocity not resolved: finite difference
+        if solution.velocity_covar.is_none() {
+            if let Some((prev_t, prev_solution)) = &self.prev_solution {
+                solution.velocity =
+                    (solution.position - prev_solution.position) / (t - *prev_t).to_seconds();
+            }
         }
 
         self.prev_solution = Some((t, solution.clone()));
@@ -600,6 +900,16 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             }
         }
     }
+    /* celestial body position, ECEF [m] */
+    fn body_position(&self, body: Bodies, t: Epoch) -> Vector3<f64> {
+        let state = self.cosmic.celestial_state(
+            body.ephem_path(),
+            t,
+            self.earth_fixed_frame,
+            LightTimeCalc::None,
+        );
+        Vector3::new(state.x_km, state.y_km, state.z_km) * 1.0E3
+    }
     /* rotate interpolated position */
     fn rotate_position(
         rotate: bool,
@@ -621,6 +931,7 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             )
         };
         reworked.position = rot * interpolated.position;
+        reworked.velocity = interpolated.velocity.map(|v| rot * v);
         reworked
     }
     /*
@@ -633,6 +944,10 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
         interpolated: InterpolationResult,
     ) -> InterpolationResult {
         let mut reworked = interpolated;
+        if reworked.velocity.is_some() {
+            // provided by user
+            return reworked;
+        }
         if let Some((p_ttx, p_pos)) = self.prev_sv_state.get(&sv) {
             let dt = (t_tx - *p_ttx).to_seconds();
             reworked.velocity = Some((interpolated.position - p_pos) / dt);
@@ -652,62 +967,4 @@ impl<I: std::ops::Fn(Epoch, SV, usize) -> Option<InterpolationResult>> Solver<I>
             pvt.velocity = Default::default();
         }
     }
-    fn retain_best_elevation(pool: &mut Vec<Candidate>, min_required: usize) {
-        //   1. Retain best elevation
-        pool.sort_by(|cd_a, cd_b| {
-            let state_a = cd_a.state.unwrap();
-            let state_b = cd_b.state.unwrap();
-            state_a.elevation.partial_cmp(&state_b.elevation).unwrap()
-        });
-
-        let mut index = 0;
-        let total = pool.len();
-
-        if min_required == 1 {
-            pool.retain(|_| {
-                index += 1;
-                index == total
-            });
-        } else {
-            pool.retain(|_| {
-                index += 1;
-                index > total - min_required
-            });
-        }
-    }
 }
-
-// #[cfg(test)]
-// mod test {
-//     use crate::prelude::{Solver, Candidate, Duration, Epoch, Observation, SV, InterpolationResult};
-//     #[test]
-//     fn retain_best_elev() {
-//         let mut pool = Vec::<Candidate>::new();
-//         for elev in [0.0, 3.0, 8.0, 16.0, 16.5, 21.0, 45.0] {
-//             let cd = Candidate::new(
-//                 SV::default(),
-//                 Epoch::default(),
-//                 Duration::default(),
-//                 None,
-//                 vec![],
-//                 vec![],
-//             );
-//             let mut state = InterpolationResult::from_position((0.0, 0.0, 0.0));
-//             state.set_elevation(elev);
-//             cd.set_state(state);
-//             pool.push(cd);
-//         }
-//
-//         for min_required in [1, 3, 4, 5] {
-//             let mut tested = pool.clone();
-//             Solver::retain_best_elevation(&mut tested, min_required);
-//             if min_required == 1 {
-//                 assert_eq!(tested.len(), 1);
-//                 assert_eq!(tested[0].state.unwrap().elevation, 45.0);
-//             } else if min_required == 3 {
-//             } else if min_required == 4 {
-//             } else if min_required == 5 {
-//             }
-//         }
-//     }
-// }
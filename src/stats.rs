@@ -0,0 +1,166 @@
+//! Statistical distributions used in integrity monitoring
+
+/// Natural logarithm of the Gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179E-2,
+        -0.5395239384953E-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut ser = 1.000000000190015_f64;
+    for (j, c) in COEFFS.iter().enumerate() {
+        ser += c / (x + 1.0 + j as f64);
+    }
+    -tmp + (2.5066282746310005 * ser / x).ln()
+}
+
+/// Regularized incomplete Gamma functions (P(a, x), Q(a, x) = 1 - P(a, x)).
+/// Both terms are evaluated directly, to preserve precision in the tails.
+fn gamma_pq(a: f64, x: f64) -> (f64, f64) {
+    const MAX_ITER: usize = 500;
+    const EPSILON: f64 = 1.0E-15;
+    if x <= 0.0 {
+        return (0.0, 1.0);
+    }
+    let gln = ln_gamma(a);
+    if x < a + 1.0 {
+        // series representation
+        let mut ap = a;
+        let mut del = 1.0 / a;
+        let mut sum = del;
+        for _ in 0..MAX_ITER {
+            ap += 1.0;
+            del *= x / ap;
+            sum += del;
+            if del.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        let p = sum * (-x + a * x.ln() - gln).exp();
+        (p, 1.0 - p)
+    } else {
+        // continued fraction representation (modified Lentz)
+        const FPMIN: f64 = 1.0E-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / FPMIN;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_ITER {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < FPMIN {
+                d = FPMIN;
+            }
+            c = b + an / c;
+            if c.abs() < FPMIN {
+                c = FPMIN;
+            }
+            d = 1.0 / d;
+            let del = d * c;
+            h *= del;
+            if (del - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        let q = (-x + a * x.ln() - gln).exp() * h;
+        (1.0 - q, q)
+    }
+}
+
+/// Cumulative distribution function of the chi-square distribution
+/// with `dof` degrees of freedom.
+pub(crate) fn chi2_cdf(x: f64, dof: usize) -> f64 {
+    gamma_pq(dof as f64 / 2.0, x / 2.0).0
+}
+
+/// Returns x so that [chi2_cdf] (x, dof) = p.
+pub(crate) fn chi2_quantile(p: f64, dof: usize) -> f64 {
+    let k = dof as f64;
+    let (mut lower, mut upper) = (0.0_f64, k + 100.0 * (2.0 * k).sqrt() + 100.0);
+    for _ in 0..200 {
+        let mid = (lower + upper) / 2.0;
+        if chi2_cdf(mid, dof) < p {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+    (lower + upper) / 2.0
+}
+
+/// Tail probability of the standard normal distribution: Q(x) = P(X > x).
+pub(crate) fn normal_q(x: f64) -> f64 {
+    if x >= 0.0 {
+        0.5 * gamma_pq(0.5, x * x / 2.0).1
+    } else {
+        1.0 - normal_q(-x)
+    }
+}
+
+/// Returns x so that [normal_q] (x) = p.
+pub(crate) fn normal_q_inv(p: f64) -> f64 {
+    let (mut lower, mut upper) = (-40.0_f64, 40.0_f64);
+    for _ in 0..200 {
+        let mid = (lower + upper) / 2.0;
+        if normal_q(mid) > p {
+            lower = mid;
+        } else {
+            upper = mid;
+        }
+    }
+    (lower + upper) / 2.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{chi2_cdf, chi2_quantile, normal_q, normal_q_inv};
+    #[test]
+    fn chi2() {
+        // reference values from chi-square tables
+        for (p, dof, expected) in [
+            (0.95, 1, 3.841),
+            (0.99, 1, 6.635),
+            (0.95, 4, 9.488),
+            (0.999, 5, 20.515),
+            (0.99, 10, 23.209),
+        ] {
+            let x = chi2_quantile(p, dof);
+            assert!(
+                (x - expected).abs() < 1.0E-3,
+                "chi2({}, {}) = {} but {} is expected",
+                p,
+                dof,
+                x,
+                expected
+            );
+            assert!((chi2_cdf(x, dof) - p).abs() < 1.0E-9);
+        }
+    }
+    #[test]
+    fn normal() {
+        // reference values from normal tables
+        for (x, expected) in [
+            (0.0, 0.5),
+            (1.0, 0.158655),
+            (-1.0, 0.841345),
+            (1.959964, 0.025),
+            (5.199338, 1.0E-7),
+        ] {
+            let q = normal_q(x);
+            assert!(
+                (q - expected).abs() / expected < 1.0E-5,
+                "Q({}) = {} but {} is expected",
+                x,
+                q,
+                expected
+            );
+            assert!((normal_q_inv(expected) - x).abs() < 1.0E-5);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::attitude::Attitude;
+use crate::prelude::{
+    Antex, Candidate, Carrier, Constellation, Duration, Epoch, InterpolationResult, PseudoRange, SV,
+};
+use nalgebra::Vector3;
+use std::str::FromStr;
+
+const ANTEX: &str = "                                                            START OF ANTENNA
+BLOCK IIF           G01                 G063      2011-036A TYPE / SERIAL NO
+     0.0                                                    DAZI
+     0.0  10.0   5.0                                        ZEN1 / ZEN2 / DZEN
+   G01                                                      START OF FREQUENCY
+    100.00      0.00   1000.00                              NORTH / EAST / UP
+   NOAZI    10.00    5.00    0.00
+   G01                                                      END OF FREQUENCY
+   G02                                                      START OF FREQUENCY
+    100.00      0.00   1200.00                              NORTH / EAST / UP
+   NOAZI    20.00   10.00    0.00
+   G02                                                      END OF FREQUENCY
+                                                            END OF ANTENNA
+                                                            START OF ANTENNA
+TRM59800.00     NONE                                        TYPE / SERIAL NO
+     0.0                                                    DAZI
+     0.0  90.0  45.0                                        ZEN1 / ZEN2 / DZEN
+   G01                                                      START OF FREQUENCY
+     10.00     -5.00     90.00                              NORTH / EAST / UP
+   NOAZI     1.00    3.00    5.00
+   G01                                                      END OF FREQUENCY
+   G02                                                      START OF FREQUENCY
+     10.00     -5.00    120.00                              NORTH / EAST / UP
+   NOAZI     2.00    4.00    6.00
+   G02                                                      END OF FREQUENCY
+                                                            END OF ANTENNA
+";
+
+#[test]
+fn sv_phase_center() {
+    let antex = Antex::from_str(ANTEX).unwrap();
+    let sv = SV::new(Constellation::GPS, 1);
+    let t = Epoch::from_str("2020-06-25T12:00:00 GPST").unwrap();
+    let antenna = antex.sv_antenna(sv, t).unwrap();
+
+    // receiver on the equator, vehicle at zenith
+    let r_rx = Vector3::new(6.378E6, 0.0, 0.0);
+    let r_sv = Vector3::new(2.6E7, 0.0, 0.0);
+    let r_sun = Vector3::new(0.0, 1.5E11, 0.0);
+
+    let pseudo_range = [Carrier::L1, Carrier::L2]
+        .iter()
+        .map(|carrier| PseudoRange {
+            carrier: *carrier,
+            value: 2.0E7,
+            snr: None,
+            code: None,
+        })
+        .collect();
+    let mut cd = Candidate::new(sv, t, Duration::default(), None, pseudo_range, vec![]);
+    cd.state = Some(InterpolationResult::from_center_of_mass((
+        r_sv[0], r_sv[1], r_sv[2],
+    )));
+    assert!(cd.state.unwrap().is_center_of_mass());
+    cd.attitude = Attitude::nominal(r_sv, r_sun);
+    cd.sv_antenna_model(antenna, r_rx);
+
+    // boresight offset towards Earth, X axis towards the Sun side
+    let l1 = cd.sv_phase_center(Carrier::L1, None);
+    assert!((l1.offset - Vector3::new(-1.0, 0.1, 0.0)).norm() < 1.0E-9);
+    // null nadir angle
+    assert!((l1.variation - 10.0E-3).abs() < 1.0E-9);
+
+    // ionosphere free combination
+    let (f_1, f_2) = (Carrier::L1.frequency(), Carrier::L2.frequency());
+    let (a, b) = (
+        f_1.powi(2) / (f_1.powi(2) - f_2.powi(2)),
+        f_2.powi(2) / (f_1.powi(2) - f_2.powi(2)),
+    );
+    let if_pc = cd.sv_phase_center(Carrier::L1, Some(Carrier::L2));
+    assert!((if_pc.offset[0] + (a * 1.0 - b * 1.2)).abs() < 1.0E-9);
+    assert!((if_pc.offset[1] - 0.1).abs() < 1.0E-9);
+    assert!((if_pc.variation - (a * 10.0E-3 - b * 20.0E-3)).abs() < 1.0E-9);
+
+    // unmodeled signal
+    let l5 = cd.sv_phase_center(Carrier::L5, None);
+    assert_eq!(l5.offset, Vector3::zeros());
+}
+
+#[test]
+fn rx_phase_center() {
+    let antex = Antex::from_str(ANTEX).unwrap();
+    let antenna = antex.rx_antenna("TRM59800.00 NONE").unwrap();
+    let sv = SV::new(Constellation::GPS, 1);
+    let t = Epoch::from_str("2020-06-25T12:00:00 GPST").unwrap();
+
+    // receiver on the equator, vehicle 45° above the northern horizon
+    let r_rx = Vector3::new(6.378E6, 0.0, 0.0);
+    let r_sv = r_rx + Vector3::new(1.0, 0.0, 1.0) * 2.0E7;
+
+    let pseudo_range = [Carrier::L1, Carrier::L2]
+        .iter()
+        .map(|carrier| PseudoRange {
+            carrier: *carrier,
+            value: 2.0E7,
+            snr: None,
+            code: None,
+        })
+        .collect();
+    let mut cd = Candidate::new(sv, t, Duration::default(), None, pseudo_range, vec![]);
+    cd.state = Some(
+        InterpolationResult::from_position((r_sv[0], r_sv[1], r_sv[2]))
+            .with_elevation_azimuth((r_rx[0], r_rx[1], r_rx[2])),
+    );
+    cd.rx_antenna_model(antenna, 0.0, 0.0);
+
+    // North, East, Up offsets rotated to ECEF
+    let l1 = cd.rx_phase_center(Carrier::L1, None);
+    assert!((l1.offset - Vector3::new(0.09, -0.005, 0.01)).norm() < 1.0E-9);
+    // 45° zenith angle
+    assert!((l1.variation - 3.0E-3).abs() < 1.0E-6);
+
+    let (f_1, f_2) = (Carrier::L1.frequency(), Carrier::L2.frequency());
+    let (a, b) = (
+        f_1.powi(2) / (f_1.powi(2) - f_2.powi(2)),
+        f_2.powi(2) / (f_1.powi(2) - f_2.powi(2)),
+    );
+    let if_pc = cd.rx_phase_center(Carrier::L1, Some(Carrier::L2));
+    assert!((if_pc.offset[0] - (a * 0.09 - b * 0.12)).abs() < 1.0E-9);
+    assert!((if_pc.variation - (a * 3.0E-3 - b * 4.0E-3)).abs() < 1.0E-6);
+
+    // satellite antenna is not modeled (APC coordinates)
+    assert_eq!(cd.sv_phase_center(Carrier::L1, None).variation, 0.0);
+}
@@ -12,6 +12,7 @@ fn test() {
         snr: None,
         value: 28776032.260,
         carrier: Carrier::E1,
+        code: None,
     };
 
     let mut cd0 = Candidate::new(
@@ -33,6 +34,7 @@ fn test() {
         snr: None,
         value: 24090441.364,
         carrier: Carrier::E1,
+        code: None,
     };
 
     let mut cd1 = Candidate::new(
@@ -54,6 +56,7 @@ fn test() {
         snr: None,
         value: 24762903.616,
         carrier: Carrier::E1,
+        code: None,
     };
 
     let mut cd2 = Candidate::new(
@@ -75,6 +78,7 @@ fn test() {
         snr: None,
         value: 25537644.454,
         carrier: Carrier::E1,
+        code: None,
     };
 
     let mut cd3 = Candidate::new(
@@ -0,0 +1,172 @@
+use crate::ambiguity::AmbiguitySolver;
+use crate::prelude::{
+    AmbiguityOpts, Candidate, Carrier, Constellation, CycleSlipDetector, Doppler, Duration, Epoch,
+    PhaseRange, PseudoRange, SlipCause, SV,
+};
+use nyx::cosmic::SPEED_OF_LIGHT;
+use std::str::FromStr;
+
+const RANGE_RATE: f64 = 500.0;
+
+/*
+ * Candidate observing a vehicle whose range increases linearly,
+ * with given slips [cycles] on each phase observation
+ */
+fn candidate(t: Epoch, dt: f64, carriers: &[Carrier], slips: &[f64]) -> Candidate {
+    let range = 2.2E7 + RANGE_RATE * dt;
+    let pseudo_range = carriers
+        .iter()
+        .map(|carrier| PseudoRange {
+            carrier: *carrier,
+            value: range,
+            snr: None,
+            code: None,
+        })
+        .collect();
+    let phase_range = carriers
+        .iter()
+        .zip(slips.iter())
+        .map(|(carrier, slip)| PhaseRange {
+            carrier: *carrier,
+            value: range + carrier.wavelength() * (100.0 + slip),
+            ambiguity: None,
+            snr: None,
+            code: None,
+            lli: None,
+            lock_time: None,
+        })
+        .collect();
+    let doppler = carriers
+        .iter()
+        .map(|carrier| Doppler {
+            carrier: *carrier,
+            value: -RANGE_RATE * carrier.frequency() / SPEED_OF_LIGHT,
+            snr: None,
+        })
+        .collect();
+    Candidate::new(
+        SV::new(Constellation::GPS, 1),
+        t,
+        Duration::default(),
+        None,
+        pseudo_range,
+        phase_range,
+    )
+    .with_doppler(doppler)
+}
+
+#[test]
+fn cycle_slip_detection() {
+    let t0 = Epoch::from_str("2020-06-25T12:00:00 GPST").unwrap();
+    for (carriers, slip, expected) in [
+        (
+            vec![Carrier::L1, Carrier::L2],
+            vec![10.0, 0.0],
+            SlipCause::MelbourneWubbena,
+        ),
+        (vec![Carrier::L1], vec![10.0], SlipCause::Doppler),
+    ] {
+        let mut detector = CycleSlipDetector::new(&AmbiguityOpts::default());
+        let no_slip = vec![0.0; carriers.len()];
+        for i in 0..30 {
+            let dt = 30.0 * i as f64;
+            let t = t0 + Duration::from_seconds(dt);
+            let slips = if i < 20 { &no_slip } else { &slip };
+            let events = detector.detect(&[candidate(t, dt, &carriers, slips)]);
+            match i {
+                0 => {
+                    assert_eq!(events.len(), 1, "first sight not declared");
+                    assert_eq!(events[0].cause, SlipCause::DataGap);
+                },
+                20 => {
+                    assert_eq!(events.len(), 1, "cycle slip not detected");
+                    assert_eq!(events[0].cause, expected);
+                    assert_eq!(events[0].t, t);
+                },
+                _ => assert!(events.is_empty(), "false alarm at epoch #{}", i),
+            }
+        }
+        // data gap
+        let dt = 30.0 * 29.0 + 600.0;
+        let t = t0 + Duration::from_seconds(dt);
+        let events = detector.detect(&[candidate(t, dt, &carriers, &slip)]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].cause, SlipCause::DataGap);
+    }
+}
+
+#[test]
+fn loss_of_lock() {
+    let t0 = Epoch::from_str("2020-06-25T12:00:00 GPST").unwrap();
+    let sv = SV::new(Constellation::GPS, 1);
+    let carriers = [Carrier::L1, Carrier::L2];
+    let opts = AmbiguityOpts::default();
+    let mut detector = CycleSlipDetector::new(&opts);
+    let mut ambiguity = AmbiguitySolver::new(&opts);
+    let mut arcs = Vec::<u16>::new();
+    for i in 0..10 {
+        let dt = 30.0 * i as f64;
+        let t = t0 + Duration::from_seconds(dt);
+        let mut cd = candidate(t, dt, &carriers, &[0.0, 0.0]);
+        for ph in cd.phase_range.iter_mut() {
+            ph.lock_time = Some(Duration::from_seconds(dt + 60.0));
+        }
+        if i == 5 {
+            cd.phase_range[1].lli = Some(0x01);
+        }
+        if i == 8 {
+            // relocked in between two observations
+            cd.phase_range[0].lock_time = Some(Duration::from_seconds(10.0));
+        }
+        let slips = detector.detect(&[cd.clone()]);
+        if i == 5 || i == 8 {
+            assert_eq!(slips.len(), 1, "loss of lock not declared");
+            assert_eq!(slips[0].cause, SlipCause::LossOfLock);
+        } else if i > 0 {
+            assert!(slips.is_empty(), "false alarm at epoch #{}", i);
+        }
+        ambiguity.resolve(&[cd], &slips);
+        arcs.push(ambiguity.phase_arc(sv).unwrap().id);
+    }
+    // tracking is re-initialized on each loss of lock
+    assert_eq!(arcs, [1, 1, 1, 1, 1, 2, 2, 2, 3, 3]);
+}
+
+#[test]
+fn receiver_clock_jump() {
+    let t0 = Epoch::from_str("2020-06-25T12:00:00 GPST").unwrap();
+    // 1 ms receiver clock jump: every observation is shifted by ~300 km
+    let jump = SPEED_OF_LIGHT * 1.0E-3;
+    let mut detector = CycleSlipDetector::new(&AmbiguityOpts::default());
+    for i in 0..10 {
+        let dt = 30.0 * i as f64;
+        let t = t0 + Duration::from_seconds(dt);
+        let pool = (1..5)
+            .map(|prn| {
+                // one actual cycle slip, at the epoch of the jump
+                let slip = if prn == 1 && i >= 5 { 10.0 } else { 0.0 };
+                let mut cd = candidate(t, dt, &[Carrier::L1], &[slip]);
+                cd.sv = SV::new(Constellation::GPS, prn);
+                if i >= 5 {
+                    for pr in cd.pseudo_range.iter_mut() {
+                        pr.value += jump;
+                    }
+                    for ph in cd.phase_range.iter_mut() {
+                        ph.value += jump;
+                    }
+                }
+                cd
+            })
+            .collect::<Vec<_>>();
+        let events = detector.detect(&pool);
+        match i {
+            0 => assert_eq!(events.len(), 4, "first sight not declared"),
+            5 => {
+                assert_eq!(events.len(), 1, "clock jump declared as cycle slips");
+                assert_eq!(events[0].sv, SV::new(Constellation::GPS, 1));
+                assert_eq!(events[0].cause, SlipCause::Doppler);
+            },
+            _ => assert!(events.is_empty(), "false alarm at epoch #{}", i),
+        }
+    }
+}
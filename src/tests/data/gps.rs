@@ -26,16 +26,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -44,18 +47,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -69,16 +81,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -87,18 +102,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -112,16 +136,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -130,18 +157,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -155,16 +191,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -173,18 +212,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -213,16 +261,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -231,18 +282,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             ambiguity: None,
                             snr: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             ambiguity: None,
                             snr: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -256,16 +316,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -274,18 +337,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             ambiguity: None,
                             snr: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -299,16 +371,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -317,18 +392,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
@@ -342,16 +426,19 @@ pub fn test_data() -> [SolverInput; 2] {
                             carrier: Carrier::L1,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                         PseudoRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
+                            code: None,
                         },
                     ],
                     vec![
@@ -360,18 +447,27 @@ pub fn test_data() -> [SolverInput; 2] {
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L2,
                             value: 1.0E6_f64,
                             ambiguity: None,
                             snr: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                         PhaseRange {
                             carrier: Carrier::L5,
                             value: 1.0E6_f64,
                             snr: None,
                             ambiguity: None,
+                            code: None,
+                            lli: None,
+                            lock_time: None,
                         },
                     ],
                 ),
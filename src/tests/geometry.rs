@@ -0,0 +1,87 @@
+//! Geometry shared by the navigation tests:
+//! one static receiver and the vehicles it observes.
+use crate::{
+    navigation::{Input, Linearization, SVInput, StateKind, StateLayout},
+    prelude::{Constellation, Vector3, SV},
+};
+use nalgebra::{DMatrix, DVector};
+
+/// Receiver position (ECEF [m])
+pub fn rx() -> Vector3<f64> {
+    Vector3::new(4_696_989.0, 723_994.0, 4_239_678.0)
+}
+
+/// First `nb_sv` (up to 8) GPS vehicles in view of [rx],
+/// with their position (ECEF [m])
+pub fn vehicles(nb_sv: usize) -> Vec<(SV, Vector3<f64>)> {
+    [
+        Vector3::new(15_000_000.0, 5_000_000.0, 21_000_000.0),
+        Vector3::new(20_000_000.0, -12_000_000.0, 10_000_000.0),
+        Vector3::new(22_000_000.0, 12_000_000.0, 5_000_000.0),
+        Vector3::new(10_000_000.0, 10_000_000.0, 22_000_000.0),
+        Vector3::new(25_000_000.0, 2_000_000.0, -4_000_000.0),
+        Vector3::new(18_000_000.0, -4_000_000.0, 18_000_000.0),
+        Vector3::new(12_000_000.0, -15_000_000.0, 17_000_000.0),
+        Vector3::new(24_000_000.0, 6_000_000.0, 12_000_000.0),
+    ]
+    .iter()
+    .take(nb_sv)
+    .enumerate()
+    .map(|(i, position)| (SV::new(Constellation::GPS, i as u8 + 1), *position))
+    .collect()
+}
+
+/// Pseudo range [Input] of these vehicles, observed from [rx]: geometric range
+/// plus `offset` [m] (clock offset, biases..), linearized around `apriori`.
+pub fn range_input<F: Fn(SV) -> f64>(
+    layout: &StateLayout,
+    vehicles: &[(SV, Vector3<f64>)],
+    offset: F,
+    apriori: &Vector3<f64>,
+) -> Input {
+    let nrows = vehicles.len();
+    Input {
+        y: DVector::<f64>::zeros(nrows),
+        g: DMatrix::<f64>::from_fn(nrows, layout.len(), |i, j| {
+            if Some(j) == layout.index(StateKind::ClockOffset)
+                || Some(j) == layout.inter_system_bias(vehicles[i].0.constellation)
+            {
+                1.0
+            } else {
+                0.0
+            }
+        }),
+        w: DMatrix::<f64>::identity(nrows, nrows),
+        sv: vehicles
+            .iter()
+            .map(|(sv, _)| (*sv, SVInput::default()))
+            .collect(),
+        rows: vehicles.iter().map(|(sv, _)| Some(*sv)).collect(),
+        layout: layout.clone(),
+        apriori: Default::default(),
+        lin: vehicles
+            .iter()
+            .map(|(sv, position)| Linearization::Range {
+                sv: *position,
+                obs: (position - rx()).norm() + offset(*sv),
+            })
+            .collect(),
+    }
+    .relinearize(apriori)
+}
+
+/// Position independent [Input]: observation matrix `g` and
+/// measurement vector `y`, one row per (evenly weighted) observation.
+pub fn fixed_input(layout: &StateLayout, g: DMatrix<f64>, y: DVector<f64>) -> Input {
+    let nrows = y.len();
+    Input {
+        y,
+        g,
+        w: DMatrix::<f64>::identity(nrows, nrows),
+        sv: Default::default(),
+        rows: vec![None; nrows],
+        layout: layout.clone(),
+        apriori: Default::default(),
+        lin: vec![Linearization::Fixed; nrows],
+    }
+}
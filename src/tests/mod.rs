@@ -1,8 +1,12 @@
 use crate::prelude::*;
 
+mod antenna;
 mod bancroft;
+mod cycle_slip;
 mod data;
+pub mod geometry;
 mod pseudo_range;
+mod windup;
 
 //pub mod cpp;
 //pub mod ppp;
@@ -123,6 +123,7 @@ fn l1_widelane() {
             codes,
             phases,
             vec![],
+            vec![],
         );
         let mw = cd.mw_combination();
         assert!(mw.is_some(), "failed to form MW combination");
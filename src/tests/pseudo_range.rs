@@ -1,4 +1,4 @@
-use crate::prelude::{Candidate, Carrier, Duration, Epoch, PseudoRange, SV};
+use crate::prelude::{Candidate, Carrier, Duration, Epoch, PhaseRange, PseudoRange, SV};
 
 use crate::candidate::PseudoRangeCombination;
 
@@ -11,22 +11,26 @@ fn prefered_pseudorange() {
                     value: 1.0,
                     snr: None,
                     carrier: Carrier::L1,
+                    code: None,
                 },
                 PseudoRange {
                     value: 2.0,
                     snr: None,
                     carrier: Carrier::L2,
+                    code: None,
                 },
                 PseudoRange {
                     value: 3.0,
                     snr: None,
                     carrier: Carrier::L5,
+                    code: None,
                 },
             ],
             PseudoRange {
                 value: 1.0,
                 snr: None,
                 carrier: Carrier::L1,
+                code: None,
             },
         ),
         (
@@ -35,22 +39,26 @@ fn prefered_pseudorange() {
                     value: 1.0,
                     snr: None,
                     carrier: Carrier::L1,
+                    code: None,
                 },
                 PseudoRange {
                     value: 2.0,
                     snr: Some(2.0),
                     carrier: Carrier::L2,
+                    code: None,
                 },
                 PseudoRange {
                     value: 3.0,
                     snr: None,
                     carrier: Carrier::L5,
+                    code: None,
                 },
             ],
             PseudoRange {
                 value: 2.0,
                 snr: Some(2.0),
                 carrier: Carrier::L2,
+                code: None,
             },
         ),
     ] {
@@ -73,11 +81,13 @@ fn l1_l2_narrowlane() {
             snr: None,
             value: 64.0,
             carrier: Carrier::L1,
+            code: None,
         },
         PseudoRange {
             snr: None,
             value: 128.0,
             carrier: Carrier::L2,
+            code: None,
         },
     ];
     let cd = Candidate::new(
@@ -102,6 +112,7 @@ fn l1_l2_narrowlane() {
         snr: None,
         value: 64.0,
         carrier: Carrier::L1,
+        code: None,
     }];
     let cd = Candidate::new(
         SV::default(),
@@ -125,11 +136,13 @@ fn e1_e5_narrowlane() {
             snr: None,
             value: 64.0,
             carrier: Carrier::E1,
+            code: None,
         },
         PseudoRange {
             snr: None,
             value: 128.0,
             carrier: Carrier::E5,
+            code: None,
         },
     ];
     let cd = Candidate::new(
@@ -150,3 +163,165 @@ fn e1_e5_narrowlane() {
             / (Carrier::E1.frequency() + Carrier::E5.frequency())
     );
 }
+
+#[test]
+fn signal_selection() {
+    let codes = vec![
+        PseudoRange {
+            snr: None,
+            value: 1.0,
+            carrier: Carrier::L5,
+            code: None,
+        },
+        PseudoRange {
+            snr: None,
+            value: 2.0,
+            carrier: Carrier::L1P,
+            code: None,
+        },
+        PseudoRange {
+            snr: None,
+            value: 3.0,
+            carrier: Carrier::L2P,
+            code: None,
+        },
+        PseudoRange {
+            snr: None,
+            value: 4.0,
+            carrier: Carrier::L1,
+            code: None,
+        },
+    ];
+    let cd = Candidate::new(
+        SV::default(),
+        Epoch::default(),
+        Duration::default(),
+        None,
+        codes,
+        vec![],
+    );
+    assert_eq!(cd.l1_pseudorange().map(|pr| pr.carrier), Some(Carrier::L1));
+    assert_eq!(cd.lj_pseudorange().map(|pr| pr.carrier), Some(Carrier::L2P));
+
+    let cmb = cd.code_if_combination().unwrap();
+    assert_eq!(cmb.reference, Carrier::L1);
+    assert_eq!(cmb.lhs, Carrier::L2P);
+}
+
+#[test]
+fn signal_priority() {
+    let codes = [
+        ("C1C", Carrier::L1, 45.0),
+        ("C1W", Carrier::L1P, 40.0),
+        ("C2W", Carrier::L2P, 35.0),
+        ("C2L", Carrier::L2, 42.0),
+        ("C5Q", Carrier::L5, 48.0),
+    ]
+    .iter()
+    .map(|(code, carrier, snr)| PseudoRange {
+        carrier: *carrier,
+        value: 20.0E6,
+        snr: Some(*snr),
+        code: Some(code.to_string()),
+    })
+    .collect::<Vec<_>>();
+    let mut cd = Candidate::new(
+        SV::default(),
+        Epoch::default(),
+        Duration::default(),
+        None,
+        codes,
+        vec![],
+    );
+    let prefered = cd.prefered_pseudorange().unwrap();
+    assert_eq!(prefered.code.as_deref(), Some("C5Q"), "best SNR");
+
+    cd.apply_signal_priority(&["1W".to_string(), "C2L".to_string(), "2W".to_string()]);
+    assert_eq!(cd.pseudo_range.len(), 3, "unlisted signals are dropped");
+
+    let prefered = cd.prefered_pseudorange().unwrap();
+    assert_eq!(prefered.code.as_deref(), Some("C1W"));
+    assert_eq!(cd.l1_pseudorange().map(|pr| pr.carrier), Some(Carrier::L1P));
+    assert_eq!(cd.lj_pseudorange().map(|pr| pr.carrier), Some(Carrier::L2));
+}
+
+#[test]
+fn observable_validation() {
+    let codes = [
+        ("C1C", Carrier::L1),
+        ("C1W", Carrier::L1),
+        ("L2W", Carrier::L2P),
+        ("C9Z", Carrier::L5),
+        ("C5Q", Carrier::L5),
+    ]
+    .iter()
+    .map(|(code, carrier)| PseudoRange {
+        carrier: *carrier,
+        value: 20.0E6,
+        snr: None,
+        code: Some(code.to_string()),
+    })
+    .collect::<Vec<_>>();
+    let mut cd = Candidate::new(
+        SV::default(),
+        Epoch::default(),
+        Duration::default(),
+        None,
+        codes,
+        vec![],
+    );
+    cd.validate_codes();
+    let codes = cd
+        .pseudo_range
+        .iter()
+        .filter_map(|pr| pr.code.as_deref())
+        .collect::<Vec<_>>();
+    assert_eq!(codes, vec!["C1C", "C5Q"]);
+}
+
+#[test]
+fn tracking_mode_pairing() {
+    let codes = [
+        ("C1C", Carrier::L1),
+        ("C1W", Carrier::L1P),
+        ("C2W", Carrier::L2P),
+        ("C2L", Carrier::L2),
+    ]
+    .iter()
+    .map(|(code, carrier)| PseudoRange {
+        carrier: *carrier,
+        value: 20.0E6,
+        snr: None,
+        code: Some(code.to_string()),
+    })
+    .collect::<Vec<_>>();
+    let phases = [("L1W", Carrier::L1P), ("L2L", Carrier::L2)]
+        .iter()
+        .map(|(code, carrier)| PhaseRange {
+            carrier: *carrier,
+            value: 20.0E6,
+            code: Some(code.to_string()),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let cd = Candidate::new(
+        SV::default(),
+        Epoch::default(),
+        Duration::default(),
+        None,
+        codes,
+        phases,
+    );
+    // C1C and C2W are preferred, but not tracked in phase
+    let code = |pr: Option<&PseudoRange>| pr.and_then(|pr| pr.code.clone());
+    let phase = |ph: Option<&PhaseRange>| ph.and_then(|ph| ph.code.clone());
+    assert_eq!(code(cd.l1_pseudorange()).as_deref(), Some("C1W"));
+    assert_eq!(phase(cd.l1_phaserange()).as_deref(), Some("L1W"));
+    assert_eq!(code(cd.lj_pseudorange()).as_deref(), Some("C2L"));
+    assert_eq!(phase(cd.lj_phaserange()).as_deref(), Some("L2L"));
+
+    let cmb = cd.code_if_combination().unwrap();
+    assert_eq!((cmb.reference, cmb.lhs), (Carrier::L1P, Carrier::L2));
+    let cmb = cd.phase_if_combination().unwrap();
+    assert_eq!((cmb.reference, cmb.lhs), (Carrier::L1P, Carrier::L2));
+}
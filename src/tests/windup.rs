@@ -0,0 +1,33 @@
+use crate::attitude::Attitude;
+use crate::prelude::{Candidate, Constellation, Duration, Epoch, InterpolationResult, SV};
+use nalgebra::Vector3;
+use std::f64::consts::PI;
+
+#[test]
+fn phase_windup() {
+    // receiver on the equator, vehicle at zenith
+    let r_rx = Vector3::new(6.378E6, 0.0, 0.0);
+    let mut cd = Candidate::new(
+        SV::new(Constellation::GPS, 1),
+        Epoch::default(),
+        Duration::default(),
+        None,
+        vec![],
+        vec![],
+    );
+    cd.state = Some(InterpolationResult::from_position((2.6E7, 0.0, 0.0)));
+
+    // Sun revolving around the vehicle boresight: one complete yaw rotation
+    let mut windup = Vec::<f64>::new();
+    for i in 0..=72 {
+        let angle = 2.0 * PI * i as f64 / 72.0;
+        let r_sun = Vector3::new(0.0, angle.cos(), angle.sin()) * 1.5E11;
+        cd.attitude = Attitude::nominal(cd.state.unwrap().position, r_sun);
+        windup.push(cd.windup_correction(r_rx, 0.0, 0.0));
+    }
+    for pair in windup.windows(2) {
+        assert!((pair[1] - pair[0]).abs() < 0.05, "discontinuous windup");
+    }
+    let total = windup[72] - windup[0];
+    assert!((total.abs() - 1.0).abs() < 1.0E-6, "windup {}", total);
+}
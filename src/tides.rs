@@ -0,0 +1,125 @@
+//! Solid Earth tides
+use crate::prelude::Epoch;
+use nalgebra::Vector3;
+
+/// Earth gravitational constant [m³/s²]
+const GM_EARTH: f64 = 3.986004415E14;
+/// Sun gravitational constant [m³/s²]
+const GM_SUN: f64 = 1.327124E20;
+/// Moon gravitational constant [m³/s²]
+const GM_MOON: f64 = 4.902801E12;
+/// Earth equatorial radius [m]
+const EARTH_RADIUS: f64 = 6378137.0;
+
+/*
+ * Displacement [m] induced by one tide generating body (IERS Conventions 2010,
+ * step 1: in phase degree 2 and 3 terms, out of phase radial terms).
+ * `r_body`: body position (ECEF [m]), `gm`: body gravitational constant,
+ * `up`: local vertical, `lat`, `lon`: station location [rad].
+ */
+fn body_displacement(
+    r_body: Vector3<f64>,
+    gm: f64,
+    up: Vector3<f64>,
+    lat: f64,
+    lon: f64,
+) -> Vector3<f64> {
+    const H3: f64 = 0.292;
+    const L3: f64 = 0.015;
+
+    let r = r_body.norm();
+    let e_body = r_body / r;
+    let k2 = gm / GM_EARTH * EARTH_RADIUS.powi(4) / r.powi(3);
+    let k3 = k2 * EARTH_RADIUS / r;
+
+    let lat_body = e_body[2].asin();
+    let lon_body = e_body[1].atan2(e_body[0]);
+
+    // nominal Love and Shida numbers, with their latitude dependence
+    let p = (3.0 * lat.sin().powi(2) - 1.0) / 2.0;
+    let h2 = 0.6078 - 0.0006 * p;
+    let l2 = 0.0847 + 0.0002 * p;
+
+    let a = e_body.dot(&up);
+
+    // degree 2, in phase
+    let mut d_body = k2 * 3.0 * l2 * a;
+    let mut d_up = k2 * (h2 * (1.5 * a.powi(2) - 0.5) - 3.0 * l2 * a.powi(2));
+
+    // degree 3, in phase
+    d_body += k3 * L3 * (7.5 * a.powi(2) - 1.5);
+    d_up += k3 * (H3 * (2.5 * a.powi(3) - 1.5 * a) - L3 * (7.5 * a.powi(2) - 1.5) * a);
+
+    // out of phase (radial only): diurnal and semi diurnal bands
+    d_up +=
+        0.75 * 0.0025 * k2 * (2.0 * lat_body).sin() * (2.0 * lat).sin() * (lon - lon_body).sin();
+    d_up += 0.75
+        * 0.0022
+        * k2
+        * lat_body.cos().powi(2)
+        * lat.cos().powi(2)
+        * (2.0 * (lon - lon_body)).sin();
+
+    e_body * d_body + up * d_up
+}
+
+/*
+ * Solid Earth tide displacement [m ECEF] of a station located at `lat`, `lon` [rad]
+ * at `t`, given the Sun and Moon positions (ECEF [m]): IERS Conventions 2010,
+ * step 1 and the main step 2 (K1) correction.
+ * Station coordinates are conventional tide free: the displacement includes
+ * the permanent tide, it is to be added to the a-priori station position.
+ */
+pub(crate) fn solid_earth_tide(
+    t: Epoch,
+    lat: f64,
+    lon: f64,
+    r_sun: Vector3<f64>,
+    r_moon: Vector3<f64>,
+) -> Vector3<f64> {
+    let up = Vector3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin());
+
+    let mut displacement = body_displacement(r_sun, GM_SUN, up, lat, lon)
+        + body_displacement(r_moon, GM_MOON, up, lat, lon);
+
+    // step 2: K1 frequency dependence of the Love number (radial)
+    let gmst = (280.46061837 + 360.98564736629 * (t.to_jde_utc_days() - 2451545.0)).to_radians();
+    displacement += up * (-0.012 * (2.0 * lat).sin() * (gmst + lon).sin());
+
+    displacement
+}
+
+#[cfg(test)]
+mod test {
+    use super::solid_earth_tide;
+    use crate::prelude::Epoch;
+    use nalgebra::Vector3;
+    use std::str::FromStr;
+    #[test]
+    fn solid_tides() {
+        let t = Epoch::from_str("2020-06-25T12:00:00 UTC").unwrap();
+        // Moon at zenith of a station on the equator, Sun on its horizon
+        let r_moon = Vector3::new(3.844E8, 0.0, 0.0);
+        let r_sun = Vector3::new(0.0, 1.496E11, 0.0);
+        let displacement = solid_earth_tide(t, 0.0, 0.0, r_sun, r_moon);
+        // uplift: about 22 cm (Moon) minus 5 cm (Sun)
+        assert!(
+            displacement[0] > 0.15 && displacement[0] < 0.19,
+            "radial {}",
+            displacement[0]
+        );
+        assert!(displacement[1].abs() < 1.0E-6);
+        assert!(displacement[2].abs() < 1.0E-6);
+
+        // station 90° away from the Moon (Sun removed): depression
+        let displacement = solid_earth_tide(t, 0.0, 90.0_f64.to_radians(), r_sun * 1.0E3, r_moon);
+        assert!(displacement[1] < 0.0);
+
+        // mid latitude: horizontal displacement towards the Moon
+        let lat = 45.0_f64.to_radians();
+        let displacement = solid_earth_tide(t, lat, 0.0, r_sun * 1.0E3, r_moon);
+        let north = Vector3::new(-lat.sin(), 0.0, lat.cos());
+        assert!(displacement.dot(&north) < -0.01);
+        assert!(displacement.norm() < 0.4);
+    }
+}